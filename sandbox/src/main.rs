@@ -44,6 +44,7 @@ use embedded_hal::{
 
 // Starting display
 use st7796_rs::*;
+use st7796_rs::rp235x::SpiConfig;
 
 /// Tell the Boot ROM about our application
 #[link_section = ".start_block"]
@@ -82,6 +83,9 @@ fn main() -> ! {
     .unwrap();
 
     let mut timer = hal::Timer::new_timer0(pac.TIMER0, &mut pac.RESETS, &clocks);
+    // Second timer just for the display driver; `timer` stays with the blink
+    // loop below
+    let mut lcd_timer = hal::Timer::new_timer1(pac.TIMER1, &mut pac.RESETS, &clocks);
 
     // The single-cycle I/O block controls our GPIO pins
     let sio = hal::Sio::new(pac.SIO);
@@ -118,8 +122,8 @@ fn main() -> ! {
     // let led_pin_d1 = pins.gpio16.into_push_pull_output();
 
     uart.write_str("Creating display SPI...\r\n").unwrap();
-    let mut display = ST7796S::new(spi_pins, clocks, pac.SPI0, &mut pac.RESETS, cs, dc, rst, timer, |s: &str| { uart.write_str(s).unwrap(); });
-    display.init();
+    let mut display = ST7796S::new_rp235x(spi_pins, &clocks, pac.SPI0, &mut pac.RESETS, SpiConfig::default(), cs, dc, rst, &mut lcd_timer);
+    display.init(&InitConfig::new().profile(InitProfile::SITRONIX)).unwrap();
 
     let rdid = display.rddid().unwrap();
     uart.write_fmt(format_args!("Display ID: {:?}\r\n", rdid)).unwrap();
@@ -0,0 +1,727 @@
+// Async ST7796S driver built on embedded-hal-async.
+//
+// Same API as the blocking `crate::ST7796S`, but every bus access and delay is
+// awaited so the panel can be driven from an Embassy task without holding the
+// CPU during init or large pixel transfers. Commands are encoded by the
+// shared `Shadow` builders in `crate::command`; this module only puts the
+// resulting transactions on an async bus.
+use core::future::{poll_fn, Future};
+use core::pin::pin;
+use core::task::Poll;
+
+use embedded_hal::digital::OutputPin;
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::digital::Wait;
+use embedded_hal_async::spi::{Operation, SpiDevice};
+
+use crate::command::{Flood, Shadow, Transaction, REPLY_MAX};
+use crate::init::{InitStep, InitSteps};
+use crate::instruction::Command;
+use crate::interface::PixelBatch;
+use crate::tearing::TE_TIMEOUT_US;
+use crate::{
+    BlankingPorch,
+    CabcMode,
+    DisplayControl,
+    DisplayFunction,
+    DriveCurrent,
+    EntryMode,
+    Error,
+    FrameRate,
+    IdleFrameRate,
+    InitConfig,
+    InstructionInput,
+    InstructionResult,
+    InversionMode,
+    NoPin,
+    Orientation,
+    PartialFrameRate,
+    PowerControl1,
+    PowerState,
+    RgbOrder,
+    Rotation,
+    ScrollArea,
+    TearingEffect,
+};
+
+/// A bus the async ST7796S command layer can talk over; the async
+/// counterpart of `crate::DisplayInterface`.
+#[allow(async_fn_in_trait)]
+pub trait DisplayInterface {
+    /// Error type of the underlying bus
+    type BusError;
+    /// Error type of the control pins (DC, RST, ...)
+    type PinError;
+
+    /// Send a single command byte.
+    async fn write_command(&mut self, cmd: u8) -> Result<(), Error<Self::BusError, Self::PinError>>;
+
+    /// Send parameter or pixel bytes following a command.
+    async fn write_data(&mut self, data: &[u8]) -> Result<(), Error<Self::BusError, Self::PinError>>;
+
+    /// Send a read command and fill `data` with the panel's reply, dummy
+    /// byte first.
+    async fn read(&mut self, cmd: u8, data: &mut [u8]) -> Result<(), Error<Self::BusError, Self::PinError>>;
+
+    /// Stream RGB565 pixels after a RAMWR/RAMWRC has been sent.
+    async fn write_pixels<I>(&mut self, pixels: I) -> Result<(), Error<Self::BusError, Self::PinError>>
+    where
+        I: IntoIterator<Item = u16>,
+    {
+        let mut batch = PixelBatch::new();
+        for px in pixels {
+            if let Some(chunk) = batch.push(px) {
+                self.write_data(chunk).await?;
+            }
+        }
+        match batch.rest() {
+            Some(chunk) => self.write_data(chunk).await,
+            None => Ok(()),
+        }
+    }
+}
+
+/// 4-wire SPI on an `embedded_hal_async` `SpiDevice`, which owns chip
+/// select; DC stays a plain output pin.
+pub struct SpiInterface<SPI, DC>
+where
+    SPI: SpiDevice,
+    DC: OutputPin,
+{
+    spi: SPI,
+    dc: DC,
+}
+
+impl<SPI, DC> SpiInterface<SPI, DC>
+where
+    SPI: SpiDevice,
+    DC: OutputPin,
+{
+    pub fn new(spi: SPI, dc: DC) -> Self {
+        SpiInterface { spi, dc }
+    }
+
+    /// Return the SPI device and DC pin.
+    pub fn release(self) -> (SPI, DC) {
+        (self.spi, self.dc)
+    }
+}
+
+impl<SPI, DC> DisplayInterface for SpiInterface<SPI, DC>
+where
+    SPI: SpiDevice,
+    DC: OutputPin,
+{
+    type BusError = SPI::Error;
+    type PinError = DC::Error;
+
+    async fn write_command(&mut self, cmd: u8) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.dc.set_low().map_err(Error::Pin)?;
+        self.spi.write(&[cmd]).await.map_err(Error::Bus)
+    }
+
+    async fn write_data(&mut self, data: &[u8]) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.dc.set_high().map_err(Error::Pin)?;
+        self.spi.write(data).await.map_err(Error::Bus)
+    }
+
+    async fn read(&mut self, cmd: u8, data: &mut [u8]) -> Result<(), Error<SPI::Error, DC::Error>> {
+        // One chip-select frame from the command byte through the reply; the
+        // panel ignores DC while it clocks data out
+        self.dc.set_low().map_err(Error::Pin)?;
+        self.spi
+            .transaction(&mut [Operation::Write(&[cmd]), Operation::Read(data)])
+            .await
+            .map_err(Error::Bus)
+    }
+}
+
+/// Async ST7796S driver over any async `DisplayInterface`, such as an
+/// `SpiInterface` on an `SpiDevice`.
+///
+/// `TE` is the optional tearing effect input, attached with `with_te_pin`.
+pub struct ST7796S<DI, RST, T, TE = NoPin<<DI as DisplayInterface>::PinError>>
+where
+    DI: DisplayInterface,
+    RST: OutputPin<Error = DI::PinError>,
+{
+    interface: DI,
+    rst: Option<RST>,
+    timer: T,
+    te: TE,
+    shadow: Shadow,
+}
+
+impl<DI, RST, T> ST7796S<DI, RST, T>
+where
+    DI: DisplayInterface,
+    RST: OutputPin<Error = DI::PinError>,
+{
+    /// Create a driver from a display interface and the reset pin.
+    pub fn new(interface: DI, rst: RST, timer: T) -> Self {
+        ST7796S {
+            interface,
+            rst: Some(rst),
+            timer,
+            te: NoPin::new(),
+            shadow: Shadow::RESET,
+        }
+    }
+
+    /// Tear the driver down, returning the display interface, the reset pin
+    /// (if there is one) and the delay provider. Detach a TE pin with
+    /// `without_te_pin` first.
+    pub fn release(self) -> (DI, Option<RST>, T) {
+        (self.interface, self.rst, self.timer)
+    }
+
+    /// Attach the panel's TE output, for `wait_for_te`.
+    pub fn with_te_pin<TE>(self, te: TE) -> ST7796S<DI, RST, T, TE> {
+        ST7796S {
+            interface: self.interface,
+            rst: self.rst,
+            timer: self.timer,
+            te,
+            shadow: self.shadow,
+        }
+    }
+}
+
+impl<DI, RST, T, TE> ST7796S<DI, RST, T, TE>
+where
+    DI: DisplayInterface,
+    RST: OutputPin<Error = DI::PinError>,
+{
+    /// Detach the TE pin, handing it back along with the driver.
+    pub fn without_te_pin(self) -> (ST7796S<DI, RST, T>, TE) {
+        let driver = ST7796S {
+            interface: self.interface,
+            rst: self.rst,
+            timer: self.timer,
+            te: NoPin::new(),
+            shadow: self.shadow,
+        };
+        (driver, self.te)
+    }
+
+    /// Sleep and display state the driver last put the panel in.
+    pub fn power_state(&self) -> PowerState {
+        self.shadow.power
+    }
+
+    /// MADCTL settings the driver last wrote.
+    pub fn orientation(&self) -> Orientation {
+        self.shadow.orientation
+    }
+
+    /// Logical (width, height) of the screen in the current orientation.
+    pub fn size(&self) -> (u16, u16) {
+        self.shadow.orientation.size()
+    }
+
+    /// Current scrolling layout, for mapping screen rows to frame memory rows.
+    pub fn scroll_area(&self) -> ScrollArea {
+        self.shadow.scroll
+    }
+
+    /// TE mode the driver last enabled, or `None` while TE is off.
+    pub fn tearing_effect(&self) -> Option<TearingEffect> {
+        self.shadow.tearing
+    }
+}
+
+impl<DI, RST, T, TE> ST7796S<DI, RST, T, TE>
+where
+    DI: DisplayInterface,
+    RST: OutputPin<Error = DI::PinError>,
+    T: DelayNs,
+{
+    /// Bring the panel up with `config`, in the same order and with the
+    /// same waits as the blocking `crate::ST7796S::init`.
+    pub async fn init(&mut self, config: &InitConfig) -> Result<(), Error<DI::BusError, DI::PinError>> {
+        let steps = InitSteps::new(config).ok_or(Error::InvalidParameter)?;
+        for step in steps {
+            match step {
+                InitStep::Reset { high, wait_ms } => {
+                    if self.reset_pin(high)? {
+                        self.delay_ms(wait_ms).await;
+                    }
+                }
+                InitStep::Send(tx) => {
+                    self.run(tx).await?;
+                }
+                InitStep::Fill(area) => self.flood(area.flood(area.height())).await?,
+            }
+        }
+        Ok(())
+    }
+
+    // Put `tx` on the bus, adopt the state it leads to and wait out its
+    // datasheet delay
+    async fn run(&mut self, tx: Transaction) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        for reg in tx.writes() {
+            self.write_command(reg.cmd).await?;
+            if !reg.data().is_empty() {
+                self.write_data(reg.data()).await?;
+            }
+        }
+        let mut words = [0u8; REPLY_MAX];
+        if let Some((cmd, len)) = tx.reply() {
+            self.read(cmd, &mut words[..len]).await?;
+        }
+        let (result, shadow) = tx.finish(&words);
+        self.shadow = shadow;
+        if tx.wait_ms() > 0 {
+            self.delay_ms(tx.wait_ms()).await;
+        }
+        Ok(result)
+    }
+
+    // Stream a flood after a RAMWR, straight to the interface so every chunk
+    // isn't traced on its own
+    async fn flood(&mut self, mut flood: Flood) -> Result<(), Error<DI::BusError, DI::PinError>> {
+        while let Some(chunk) = flood.next_chunk() {
+            self.interface.write_data(chunk).await?;
+        }
+        Ok(())
+    }
+
+    // Drive the reset pin, if there is one. Returns whether there was.
+    fn reset_pin(&mut self, high: bool) -> Result<bool, Error<DI::BusError, DI::PinError>> {
+        let rst = match self.rst.as_mut() {
+            Some(rst) => rst,
+            None => return Ok(false),
+        };
+        if high {
+            rst.set_high().map_err(Error::Pin)?;
+        } else {
+            rst.set_low().map_err(Error::Pin)?;
+            self.shadow = Shadow::RESET;
+        }
+        Ok(true)
+    }
+
+    // Helper function to write a command to the display interface
+    async fn write_command(&mut self, cmd: u8) -> Result<(), Error<DI::BusError, DI::PinError>> {
+        trace!("cmd {:#x}", cmd);
+        self.interface.write_command(cmd).await
+    }
+
+    // Helper function to write data to the display interface
+    async fn write_data(&mut self, data: &[u8]) -> Result<(), Error<DI::BusError, DI::PinError>> {
+        trace!("data {:?}", data);
+        self.interface.write_data(data).await
+    }
+
+    // Helper function to send a read command and read back its reply
+    async fn read(&mut self, cmd: u8, data: &mut [u8]) -> Result<(), Error<DI::BusError, DI::PinError>> {
+        self.interface.read(cmd, data).await?;
+        trace!("read {:#x} -> {:?}", cmd, data);
+        Ok(())
+    }
+
+    // Wait out a datasheet timing requirement
+    async fn delay_ms(&mut self, ms: u32) {
+        debug!("wait {} ms", ms);
+        self.timer.delay_ms(ms).await;
+    }
+
+    /// Read the panel's power mode (RDDPM) and adopt it as the tracked state,
+    /// for taking over a panel that is already running without `init`.
+    #[cold]
+    pub async fn sync_power_state(&mut self) -> Result<PowerState, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.sync_power_state()).await?;
+        Ok(self.shadow.power)
+    }
+
+    pub async fn exec(&mut self, command: Command, _inp: InstructionInput) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.exec(command)?).await
+    }
+
+    /// Set the CASET/RASET window that following pixel writes land in; see
+    /// `crate::ST7796S::set_window`.
+    pub async fn set_window(&mut self, x0: u16, y0: u16, x1: u16, y1: u16) -> Result<(), Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.set_window(x0, y0, x1, y1)?).await?;
+        Ok(())
+    }
+
+    /// Set rotation, horizontal mirroring and colour order (MADCTL); see
+    /// `crate::ST7796S::set_orientation`.
+    pub async fn set_orientation(&mut self, rotation: Rotation, mirror: bool, rgb_order: RgbOrder) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.set_orientation(rotation, mirror, rgb_order)).await
+    }
+
+    /// Start a RAMWR and stream RGB565 pixels into the current window.
+    pub async fn write_pixels<I>(&mut self, pixels: I) -> Result<(), Error<DI::BusError, DI::PinError>>
+    where
+        I: IntoIterator<Item = u16>,
+    {
+        self.write_command(Command::RAMWR.into()).await?;
+        debug!("streaming pixels");
+        self.interface.write_pixels(pixels).await
+    }
+
+    /// Start a RAMWR and write `count` RGB565 pixels of a single colour.
+    pub async fn fill(&mut self, color: u16, count: u32) -> Result<(), Error<DI::BusError, DI::PinError>> {
+        self.write_command(Command::RAMWR.into()).await?;
+        self.flood(Flood::new(&color.to_be_bytes(), count)).await
+    }
+
+    /// Send RAMRD and read raw frame memory from the current window, dummy
+    /// byte first; see `crate::ST7796S::ramrd`.
+    #[cold]
+    pub async fn ramrd(&mut self, data: &mut [u8]) -> Result<(), Error<DI::BusError, DI::PinError>> {
+        self.read(Command::RAMRD.into(), data).await
+    }
+
+    // No operation
+    pub async fn nop(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.nop()).await
+    }
+
+    // Software reset. Puts the panel back into sleep-in with the display off
+    // and waits the 120 ms required before SLPOUT.
+    pub async fn swreset(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.swreset()).await
+    }
+
+    // Read Display ID
+    #[cold]
+    pub async fn rddid(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.rddid()).await
+    }
+
+    // Read Number of the Errors on DSI
+    #[cold]
+    pub async fn rnedsi(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.rnedsi()).await
+    }
+
+    // Read Display Status
+    #[cold]
+    pub async fn rddst(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.rddst()).await
+    }
+
+    // Read Display Power Mode
+    #[cold]
+    pub async fn rddpm(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.rddpm()).await
+    }
+
+    // Read Display MADCTL
+    #[cold]
+    pub async fn rddmadctl(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.rddmadctl()).await
+    }
+
+    // Read Display Pixel Format
+    #[cold]
+    pub async fn rddpixfmt(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.rddpixfmt()).await
+    }
+
+    // Read Display Image Mode
+    #[cold]
+    pub async fn rddim(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.rddim()).await
+    }
+
+    // Read Display Signal Mode
+    #[cold]
+    pub async fn rddsm(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.rddsm()).await
+    }
+
+    // Read Display Self-Diagnostic Result
+    #[cold]
+    pub async fn rddsdr(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.rddsdr()).await
+    }
+
+    // Enter inversion off mode
+    pub async fn invoff(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.invoff()).await
+    }
+
+    // Enter inversion on mode
+    pub async fn invon(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.invon()).await
+    }
+
+    // Enter display off mode
+    pub async fn dispoff(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.dispoff()).await
+    }
+
+    // Enter display on mode. Fails with `Error::InvalidState` while the panel
+    // is asleep.
+    pub async fn dispon(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.dispon()?).await
+    }
+
+    /// Enter sleep mode (SLPIN), turning the display off first and waiting
+    /// the 120 ms required before SLPOUT.
+    pub async fn sleep_in(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.sleep_in()).await
+    }
+
+    /// Leave sleep mode (SLPOUT) and wait 120 ms for the supplies to settle.
+    pub async fn sleep_out(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.sleep_out()).await
+    }
+
+    /// Set the frame memory rows shown in partial mode (PTLAR); see
+    /// `crate::ST7796S::ptlar`.
+    pub async fn ptlar(&mut self, start_row: u16, end_row: u16) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.ptlar(start_row, end_row)?).await
+    }
+
+    // Enter partial mode
+    pub async fn ptlon(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.ptlon()).await
+    }
+
+    // Leave partial mode
+    pub async fn noron(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.noron()).await
+    }
+
+    // Enter idle mode (8 colours, FRMCTR2 frame rate)
+    pub async fn idmon(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.idmon()).await
+    }
+
+    // Leave idle mode and show full colours again
+    pub async fn idmoff(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.idmoff()).await
+    }
+
+    /// Set the idle mode frame rate (FRMCTR2).
+    pub async fn frmctr2(&mut self, frame_rate: IdleFrameRate) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.frmctr2(frame_rate)?).await
+    }
+
+    /// Set the normal mode frame rate (FRMCTR1).
+    pub async fn frmctr1(&mut self, frame_rate: FrameRate) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.frmctr1(frame_rate)?).await
+    }
+
+    /// Set the partial mode frame rate (FRMCTR3).
+    pub async fn frmctr3(&mut self, frame_rate: PartialFrameRate) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.frmctr3(frame_rate)?).await
+    }
+
+    /// Select column, 1-dot or 2-dot inversion (INVCTR).
+    pub async fn invctr(&mut self, mode: InversionMode) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.invctr(mode)).await
+    }
+
+    /// Set the blanking porches (BPC).
+    pub async fn bpc(&mut self, porch: BlankingPorch) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.bpc(porch)?).await
+    }
+
+    /// Set scan directions, driven lines and RGB interface options (DFC).
+    pub async fn dfc(&mut self, function: DisplayFunction) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.dfc(function)?).await
+    }
+
+    /// Set the 16- to 18-bit colour expansion and gate output level (EM).
+    pub async fn em(&mut self, mode: EntryMode) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.em(mode)).await
+    }
+
+    /// Set the AVDD/AVCL and VGH/VGL charge pumps (PWCTR1).
+    pub async fn pwctr1(&mut self, power: PowerControl1) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.pwctr1(power)?).await
+    }
+
+    /// Set GVDD as `gvdd_mv` above VCOM (PWCTR2); see
+    /// `crate::ST7796S::pwctr2`.
+    pub async fn pwctr2(&mut self, gvdd_mv: u16) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.pwctr2(gvdd_mv)?).await
+    }
+
+    /// Set the source and gamma driver currents in normal mode (PWCTR3).
+    pub async fn pwctr3(&mut self, source: DriveCurrent, gamma: DriveCurrent) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.pwctr3(source, gamma)).await
+    }
+
+    /// Set the VCOM voltage (VCMPCTR); see `crate::ST7796S::vcmpctr`.
+    pub async fn vcmpctr(&mut self, vcom_mv: u16) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.vcmpctr(vcom_mv)?).await
+    }
+
+    /// Trim VCOM by `offset_mv` (VCMOFFS), or use the NV memory offset with
+    /// `None`; see `crate::ST7796S::vcmoffs`.
+    pub async fn vcmoffs(&mut self, offset_mv: Option<i16>) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.vcmoffs(offset_mv)?).await
+    }
+
+    /// Define the top fixed, scrolling and bottom fixed areas (VSCRDER); see
+    /// `crate::ST7796S::vscrder`.
+    pub async fn vscrder(&mut self, top_fixed: u16, scroll_height: u16, bottom_fixed: u16) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.vscrder(top_fixed, scroll_height, bottom_fixed)?).await
+    }
+
+    /// Set the frame memory row shown at the top of the scrolling area
+    /// (VSCRSADD).
+    pub async fn vscrsadd(&mut self, start: u16) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.vscrsadd(start)?).await
+    }
+
+    /// Turn the TE output on (TEON); see `crate::ST7796S::teon`.
+    pub async fn teon(&mut self, mode: TearingEffect) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.teon(mode)).await
+    }
+
+    // Turn the TE output off
+    pub async fn teoff(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.teoff()).await
+    }
+
+    /// Raise TE when the refresh reaches frame memory row `line` (STE); see
+    /// `crate::ST7796S::tescan`.
+    pub async fn tescan(&mut self, line: u16) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.tescan(line)?).await
+    }
+
+    // Read the frame memory row the refresh is currently on
+    pub async fn rdtescan(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.rdtescan()).await
+    }
+
+    /// Set the display brightness, 0 (darkest) to 255 (WRDISBV).
+    pub async fn wrdisbv(&mut self, val: u8) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.wrdisbv(val)).await
+    }
+
+    // Read Display Brightness Value
+    pub async fn rddisbv(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.rddisbv()).await
+    }
+
+    /// Switch the brightness block, dimming and backlight (WRCTRLD).
+    pub async fn wrctrld(&mut self, ctrl: DisplayControl) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.wrctrld(ctrl)).await
+    }
+
+    // Read CTRL Display
+    pub async fn rdctrld(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.rdctrld()).await
+    }
+
+    /// Select the content adaptive brightness mode (WRCABC).
+    pub async fn wrcabc(&mut self, mode: CabcMode) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.wrcabc(mode)).await
+    }
+
+    // Read Content Adaptive Brightness Control
+    pub async fn rdcabc(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.rdcabc()).await
+    }
+
+    /// Set the lowest brightness CABC may dim to, 0 to 255 (WRCABCMB).
+    pub async fn wrcabcmb(&mut self, val: u8) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.wrcabcmb(val)).await
+    }
+
+    // Read CABC Minimum Brightness
+    pub async fn rdcabcmb(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.rdcabcmb()).await
+    }
+}
+
+impl<DI, RST, T, TE> ST7796S<DI, RST, T, TE>
+where
+    DI: DisplayInterface,
+    RST: OutputPin<Error = DI::PinError>,
+    T: DelayNs,
+    TE: Wait<Error = DI::PinError>,
+{
+    /// Wait for the next rising edge on the TE pin attached with
+    /// `with_te_pin`, giving up with `Error::Timeout` after
+    /// `TE_TIMEOUT_US`; see `crate::ST7796S::wait_for_te`.
+    pub async fn wait_for_te(&mut self) -> Result<(), Error<DI::BusError, DI::PinError>> {
+        self.shadow.te_running()?;
+        let mut edge = pin!(self.te.wait_for_rising_edge());
+        let mut timeout = pin!(self.timer.delay_us(TE_TIMEOUT_US));
+        let res = poll_fn(|cx| {
+            if let Poll::Ready(res) = edge.as_mut().poll(cx) {
+                return Poll::Ready(Some(res));
+            }
+            timeout.as_mut().poll(cx).map(|()| None)
+        })
+        .await;
+        match res {
+            Some(res) => {
+                res.map_err(Error::Pin)?;
+                trace!("TE edge");
+                Ok(())
+            }
+            None => {
+                warn!("TE wait timed out");
+                Err(Error::Timeout)
+            }
+        }
+    }
+}
+
+impl<DI, T> ST7796S<DI, NoPin<DI::PinError>, T>
+where
+    DI: DisplayInterface,
+    DI::PinError: embedded_hal::digital::Error,
+{
+    /// Create a driver for a module whose RESET isn't connected to a GPIO;
+    /// `init` relies on SWRESET alone. Use `sync_power_state` to take over a
+    /// panel that is already running.
+    pub fn new_without_reset(interface: DI, timer: T) -> Self {
+        ST7796S {
+            interface,
+            rst: None,
+            timer,
+            te: NoPin::new(),
+            shadow: Shadow::RESET,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{self, block_on};
+    use crate::InitProfile;
+
+    #[test]
+    fn init_matches_the_blocking_driver() {
+        let config = InitConfig::new().rotation(Rotation::Deg90).profile(InitProfile::SITRONIX);
+
+        let (mut display, log) = mock::async_display();
+        block_on(display.init(&config)).unwrap();
+
+        let (mut blocking, reference) = mock::display();
+        blocking.init(&config).unwrap();
+        assert_eq!(log.ops(), reference.ops());
+        assert_eq!(display.power_state(), blocking.power_state());
+        assert_eq!(display.orientation(), blocking.orientation());
+    }
+
+    #[test]
+    fn exec_matches_the_blocking_driver() {
+        let reply = [0x00, 0x9C, 0x53, 0x06, 0x00];
+
+        let (mut display, log) = mock::async_display();
+        display.interface.reply(&reply);
+        let result = block_on(display.exec(Command::RDDST, InstructionInput::NoInput)).unwrap();
+        block_on(display.exec(Command::SWRESET, InstructionInput::NoInput)).unwrap();
+
+        let (mut blocking, reference) = mock::display();
+        blocking.interface().reply(&reply);
+        let expected = blocking.exec(Command::RDDST, InstructionInput::NoInput).unwrap();
+        blocking.exec(Command::SWRESET, InstructionInput::NoInput).unwrap();
+
+        assert_eq!(result, expected);
+        assert_eq!(log.ops(), reference.ops());
+    }
+}
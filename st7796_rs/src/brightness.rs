@@ -0,0 +1,223 @@
+// Backlight brightness and content adaptive brightness control (CABC).
+//
+// The ST7796S drives an external backlight controller through its LEDPWM
+// output: WRCTRLD enables the brightness block, WRDISBV sets the level and
+// WRCABC lets the panel lower it further depending on picture content. Many
+// modules leave LEDPWM unconnected and wire the backlight LED to a GPIO
+// instead; `PwmBacklight` drives such a pin from a PWM channel.
+use embedded_hal::digital::OutputPin;
+use embedded_hal::pwm::SetDutyCycle;
+
+use crate::command::{words, Shadow, Transaction};
+use crate::instruction::Command;
+use crate::{bit, DisplayInterface, Error, InstructionResult, OnOff, ST7796S};
+
+// WRCTRLD bits
+const CTRLD_BCTRL: u8 = 0x20;
+const CTRLD_DD: u8 = 0x08;
+const CTRLD_BL: u8 = 0x04;
+
+/// Brightness control bits written with WRCTRLD and read with RDCTRLD.
+///
+/// All three are off after reset, which leaves LEDPWM low.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DisplayControl {
+    pub bctrl: OnOff,                 // Brightness control block
+    pub dd: OnOff,                    // Display dimming
+    pub bl: OnOff,                    // Backlight
+}
+
+impl DisplayControl {
+    /// Brightness block and backlight on, no dimming.
+    pub const ON: DisplayControl = DisplayControl {
+        bctrl: OnOff::ON,
+        dd: OnOff::OFF,
+        bl: OnOff::ON,
+    };
+
+    // Decode the dummy byte plus control byte returned by RDCTRLD
+    pub(crate) fn from_words(ret_words: &[u8; 2]) -> Self {
+        let on = |mask| match ret_words[1] & mask {
+            0 => OnOff::OFF,
+            _ => OnOff::ON,
+        };
+        DisplayControl {
+            bctrl: on(CTRLD_BCTRL),
+            dd: on(CTRLD_DD),
+            bl: on(CTRLD_BL),
+        }
+    }
+
+    /// The WRCTRLD parameter byte, as RDCTRLD returns it after its dummy byte.
+    pub fn to_bytes(&self) -> [u8; 1] {
+        [bit(self.bctrl == OnOff::ON, CTRLD_BCTRL)
+            | bit(self.dd == OnOff::ON, CTRLD_DD)
+            | bit(self.bl == OnOff::ON, CTRLD_BL)]
+    }
+}
+
+/// Content adaptive brightness control mode (WRCABC C[1:0]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CabcMode {
+    OFF,
+    UI,
+    STILL,
+    MOVING,
+}
+
+impl CabcMode {
+    // Decode the dummy byte plus mode byte returned by RDCABC
+    pub(crate) fn from_words(ret_words: &[u8; 2]) -> Self {
+        match ret_words[1] & 0x03 {
+            0 => CabcMode::OFF,
+            1 => CabcMode::UI,
+            2 => CabcMode::STILL,
+            _ => CabcMode::MOVING,
+        }
+    }
+
+    /// The WRCABC parameter byte, as RDCABC returns it after its dummy byte.
+    pub fn to_bytes(&self) -> [u8; 1] {
+        match self {
+            CabcMode::OFF => [0x00],
+            CabcMode::UI => [0x01],
+            CabcMode::STILL => [0x02],
+            CabcMode::MOVING => [0x03],
+        }
+    }
+}
+
+/// Backlight driven by a PWM channel, for modules whose LEDPWM output isn't
+/// wired to the backlight.
+///
+/// Brightness levels use the same 0-255 scale as WRDISBV.
+pub struct PwmBacklight<P> {
+    pwm: P,
+}
+
+impl<P> PwmBacklight<P>
+where
+    P: SetDutyCycle,
+{
+    pub fn new(pwm: P) -> Self {
+        PwmBacklight { pwm }
+    }
+
+    /// Set the duty cycle to `level` / 255.
+    pub fn set_brightness(&mut self, level: u8) -> Result<(), P::Error> {
+        self.pwm.set_duty_cycle_fraction(level as u16, u8::MAX as u16)
+    }
+
+    /// Switch the backlight fully off.
+    pub fn off(&mut self) -> Result<(), P::Error> {
+        self.pwm.set_duty_cycle_fully_off()
+    }
+
+    /// Hand back the PWM channel.
+    pub fn release(self) -> P {
+        self.pwm
+    }
+}
+
+impl Shadow {
+    pub(crate) fn wrdisbv(&self, val: u8) -> Transaction {
+        self.keep().write(Command::WRDISBV, &[val])
+    }
+
+    pub(crate) fn rddisbv(&self) -> Transaction {
+        self.keep().read(Command::RDDISBV, 2, |w| InstructionResult::RDDISBVReturn(w[1]))
+    }
+
+    pub(crate) fn wrctrld(&self, ctrl: DisplayControl) -> Transaction {
+        self.keep().write(Command::WRCTRLD, &ctrl.to_bytes())
+    }
+
+    pub(crate) fn rdctrld(&self) -> Transaction {
+        self.keep().read(Command::RDCTRLD, 2, |w| InstructionResult::RDCTRLDReturn(DisplayControl::from_words(&words(w))))
+    }
+
+    pub(crate) fn wrcabc(&self, mode: CabcMode) -> Transaction {
+        self.keep().write(Command::WRCABC, &mode.to_bytes())
+    }
+
+    pub(crate) fn rdcabc(&self) -> Transaction {
+        self.keep().read(Command::RDCABC, 2, |w| InstructionResult::RDCABCReturn(CabcMode::from_words(&words(w))))
+    }
+
+    pub(crate) fn wrcabcmb(&self, val: u8) -> Transaction {
+        self.keep().write(Command::WRCABCMB, &[val])
+    }
+
+    pub(crate) fn rdcabcmb(&self) -> Transaction {
+        self.keep().read(Command::RDCABCMB, 2, |w| InstructionResult::RDCABCMBReturn(w[1]))
+    }
+}
+
+impl<DI, RST, T, TE> ST7796S<DI, RST, T, TE>
+where
+    DI: DisplayInterface,
+    RST: OutputPin<Error = DI::PinError>,
+{
+    /// Set the display brightness, 0 (darkest) to 255 (WRDISBV).
+    ///
+    /// Only has an effect while BCTRL is on; see `wrctrld`.
+    pub fn wrdisbv(&mut self, val: u8) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.wrdisbv(val))
+    }
+
+    // Read Display Brightness Value
+    pub fn rddisbv(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.rddisbv())
+    }
+
+    /// Switch the brightness block, dimming and backlight (WRCTRLD).
+    pub fn wrctrld(&mut self, ctrl: DisplayControl) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.wrctrld(ctrl))
+    }
+
+    // Read CTRL Display
+    pub fn rdctrld(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.rdctrld())
+    }
+
+    /// Select the content adaptive brightness mode (WRCABC).
+    pub fn wrcabc(&mut self, mode: CabcMode) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.wrcabc(mode))
+    }
+
+    // Read Content Adaptive Brightness Control
+    pub fn rdcabc(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.rdcabc())
+    }
+
+    /// Set the lowest brightness CABC may dim to, 0 to 255 (WRCABCMB).
+    pub fn wrcabcmb(&mut self, val: u8) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.wrcabcmb(val))
+    }
+
+    // Read CABC Minimum Brightness
+    pub fn rdcabcmb(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.rdcabcmb())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_control_round_trips() {
+        for b in 0..=u8::MAX {
+            assert_eq!(DisplayControl::from_words(&[0, b]).to_bytes(), [b & 0x2C]);
+        }
+    }
+
+    #[test]
+    fn cabc_mode_round_trips() {
+        for b in 0..=3 {
+            assert_eq!(CabcMode::from_words(&[0, b]).to_bytes(), [b]);
+        }
+    }
+}
@@ -0,0 +1,493 @@
+// Bus-independent half of the command layer, shared by the blocking
+// `crate::ST7796S` and `crate::asynch::ST7796S`.
+//
+// Every command is encoded once, as a `Transaction` built from the driver's
+// `Shadow` of the panel state: the command and parameter writes, the reply to
+// read back and how to decode it, the wait the datasheet asks for afterwards
+// and the shadow state once it has gone through. The two drivers only differ
+// in how they put a transaction on the bus.
+use crate::config::HEIGHT;
+use crate::instruction::Command;
+use crate::{
+    DisplayMode,
+    Error,
+    IdleFrameRate,
+    InOut,
+    InstructionResult,
+    OnOff,
+    Orientation,
+    PowerState,
+    RDDIDResult,
+    RDDIMResult,
+    RDDMADCTLResult,
+    RDDPIXFMTResult,
+    RDDPMResult,
+    RDDSDRResult,
+    RDDSMResult,
+    RDDSTResult,
+    RNEDSIResult,
+    RgbOrder,
+    Rotation,
+    ScrollArea,
+    TearingEffect,
+};
+
+// Most writes a single command needs: an extended register between the two
+// CSCON unlock and the two CSCON lock writes
+const MAX_WRITES: usize = 5;
+
+// Longest reply read back, dummy byte included (RDDST)
+pub(crate) const REPLY_MAX: usize = 5;
+
+// Size of the stack buffer used when flooding GRAM; a whole number of both
+// 2- and 3-byte pixels
+const FILL_CHUNK: usize = 96;
+
+// Longest parameter list of any command the driver sends (PGC/NGC)
+const MAX_PARAMS: usize = 14;
+
+// Panel state the driver tracks on the host side, as last commanded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Shadow {
+    pub(crate) power: PowerState,
+    pub(crate) scroll: ScrollArea,
+    pub(crate) tearing: Option<TearingEffect>,
+    pub(crate) orientation: Orientation,
+}
+
+// Why a command was refused before anything went on the bus
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Rejected {
+    InvalidParameter,
+    InvalidState,
+    UnsupportedCommand,
+}
+
+impl<BusE, PinE> From<Rejected> for Error<BusE, PinE> {
+    fn from(rejected: Rejected) -> Self {
+        match rejected {
+            Rejected::InvalidParameter => Error::InvalidParameter,
+            Rejected::InvalidState => Error::InvalidState,
+            Rejected::UnsupportedCommand => Error::UnsupportedCommand,
+        }
+    }
+}
+
+// One buffered command and its parameters
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RegisterWrite {
+    pub(crate) cmd: u8,
+    data: [u8; MAX_PARAMS],
+    len: usize,
+}
+
+impl RegisterWrite {
+    // Placeholder for unused slots
+    pub(crate) const EMPTY: RegisterWrite = RegisterWrite {
+        cmd: 0,
+        data: [0; MAX_PARAMS],
+        len: 0,
+    };
+
+    pub(crate) fn new(cmd: Command, bytes: &[u8]) -> Self {
+        let mut data = [0u8; MAX_PARAMS];
+        data[..bytes.len()].copy_from_slice(bytes);
+        RegisterWrite {
+            cmd: cmd.into(),
+            data,
+            len: bytes.len(),
+        }
+    }
+
+    pub(crate) fn data(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+}
+
+// A read command, the length of its reply and how to decode it
+#[derive(Clone, Copy, Debug)]
+struct Reply {
+    cmd: u8,
+    len: usize,
+    decode: fn(&[u8; REPLY_MAX]) -> InstructionResult,
+    // Shadow state the reply implies, for reads that resync the driver
+    adopt: Option<fn(Shadow, &[u8; REPLY_MAX]) -> Shadow>,
+}
+
+// Everything one driver method sends and reads, in bus order
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Transaction {
+    writes: [RegisterWrite; MAX_WRITES],
+    count: usize,
+    reply: Option<Reply>,
+    wait_ms: u32,
+    next: Shadow,
+}
+
+impl Transaction {
+    // Nothing to send yet; leaves the shadow state at `next`
+    pub(crate) fn new(next: Shadow) -> Self {
+        Transaction {
+            writes: [RegisterWrite::EMPTY; MAX_WRITES],
+            count: 0,
+            reply: None,
+            wait_ms: 0,
+            next,
+        }
+    }
+
+    // Append a command and its parameters
+    pub(crate) fn write(mut self, cmd: Command, data: &[u8]) -> Self {
+        self.writes[self.count] = RegisterWrite::new(cmd, data);
+        self.count += 1;
+        self
+    }
+
+    // Append a buffered register write
+    pub(crate) fn register(mut self, reg: RegisterWrite) -> Self {
+        self.writes[self.count] = reg;
+        self.count += 1;
+        self
+    }
+
+    // Append RAMWR, which pixel data then follows
+    pub(crate) fn ramwr(self) -> Self {
+        self.write(Command::RAMWR, &[])
+    }
+
+    // Enable the extended command set (CSCON 0xC3/0x96) needed for the
+    // 0xB0-0xFF registers
+    pub(crate) fn unlock(self) -> Self {
+        self.write(Command::CSCON, &[0xC3]).write(Command::CSCON, &[0x96])
+    }
+
+    // Disable the extended command set again
+    pub(crate) fn lock(self) -> Self {
+        self.write(Command::CSCON, &[0x3C]).write(Command::CSCON, &[0x69])
+    }
+
+    // Append one register of the extended command set, unlocking it around
+    // the write
+    pub(crate) fn extended(self, cmd: Command, data: &[u8]) -> Self {
+        self.unlock().write(cmd, data).lock()
+    }
+
+    // Read `len` reply bytes after `cmd`, dummy byte included
+    pub(crate) fn read(mut self, cmd: Command, len: usize, decode: fn(&[u8; REPLY_MAX]) -> InstructionResult) -> Self {
+        self.reply = Some(Reply {
+            cmd: cmd.into(),
+            len,
+            decode,
+            adopt: None,
+        });
+        self
+    }
+
+    // Take the shadow state from the reply instead of `next`
+    fn adopt(mut self, adopt: fn(Shadow, &[u8; REPLY_MAX]) -> Shadow) -> Self {
+        if let Some(reply) = self.reply.as_mut() {
+            reply.adopt = Some(adopt);
+        }
+        self
+    }
+
+    // Wait `ms` once everything has been sent
+    fn wait(mut self, ms: u32) -> Self {
+        self.wait_ms = ms;
+        self
+    }
+
+    // The command/parameter writes, in order
+    pub(crate) fn writes(&self) -> &[RegisterWrite] {
+        &self.writes[..self.count]
+    }
+
+    // The read command and reply length, if the transaction reads back
+    pub(crate) fn reply(&self) -> Option<(u8, usize)> {
+        self.reply.map(|r| (r.cmd, r.len))
+    }
+
+    // How long the panel needs before the next command
+    pub(crate) fn wait_ms(&self) -> u32 {
+        self.wait_ms
+    }
+
+    // Shadow state the transaction leaves behind, assuming it goes through
+    pub(crate) fn next(&self) -> Shadow {
+        self.next
+    }
+
+    // Decode the reply bytes read back (all zero without a reply) into the
+    // result and the shadow state to adopt
+    pub(crate) fn finish(&self, words: &[u8; REPLY_MAX]) -> (InstructionResult, Shadow) {
+        match self.reply {
+            Some(reply) => {
+                let next = match reply.adopt {
+                    Some(adopt) => adopt(self.next, words),
+                    None => self.next,
+                };
+                ((reply.decode)(words), next)
+            }
+            None => (InstructionResult::NoReturn, self.next),
+        }
+    }
+}
+
+// The first `N` reply bytes, for the fixed-size `from_words` decoders
+pub(crate) fn words<const N: usize>(reply: &[u8; REPLY_MAX]) -> [u8; N] {
+    core::array::from_fn(|i| reply[i])
+}
+
+// One pixel's worth of bytes repeated `count` times, handed out in
+// `FILL_CHUNK`-sized writes after a RAMWR
+pub(crate) struct Flood {
+    chunk: [u8; FILL_CHUNK],
+    pixel_len: usize,
+    remaining: usize,
+}
+
+impl Flood {
+    pub(crate) fn new(pixel: &[u8], count: u32) -> Self {
+        debug!("fill {} px with {:?}", count, pixel);
+        let mut chunk = [0u8; FILL_CHUNK];
+        for px in chunk.chunks_exact_mut(pixel.len()) {
+            px.copy_from_slice(pixel);
+        }
+        Flood {
+            chunk,
+            pixel_len: pixel.len(),
+            remaining: count as usize,
+        }
+    }
+
+    // The next write, or `None` once every pixel has been handed out
+    pub(crate) fn next_chunk(&mut self) -> Option<&[u8]> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let n = self.remaining.min(FILL_CHUNK / self.pixel_len);
+        self.remaining -= n;
+        Some(&self.chunk[..n * self.pixel_len])
+    }
+}
+
+impl Shadow {
+    // Everything a reset puts back to its default
+    pub(crate) const RESET: Shadow = Shadow {
+        power: PowerState::RESET,
+        scroll: ScrollArea::FULL,
+        tearing: None,
+        orientation: Orientation::RESET,
+    };
+
+    // A transaction that leaves the shadow state as it is
+    pub(crate) fn keep(&self) -> Transaction {
+        Transaction::new(*self)
+    }
+
+    // A transaction that leaves the power state at `power`
+    fn with_power(&self, power: PowerState) -> Transaction {
+        Transaction::new(Shadow { power, ..*self })
+    }
+
+    pub(crate) fn nop(&self) -> Transaction {
+        self.keep().write(Command::NOP, &[])
+    }
+
+    // SWRESET, then the 120 ms required before SLPOUT
+    pub(crate) fn swreset(&self) -> Transaction {
+        Transaction::new(Shadow::RESET).write(Command::SWRESET, &[]).wait(120)
+    }
+
+    // RDDPM, adopting the reported power state
+    pub(crate) fn sync_power_state(&self) -> Transaction {
+        self.rddpm().adopt(|shadow, w| Shadow {
+            power: PowerState::from_rddpm(&RDDPMResult::from_words(&words(w))),
+            ..shadow
+        })
+    }
+
+    pub(crate) fn rddid(&self) -> Transaction {
+        self.keep().read(Command::RDDID, 4, |w| InstructionResult::RDDIDReturn(RDDIDResult::from_words(&words(w))))
+    }
+
+    pub(crate) fn rnedsi(&self) -> Transaction {
+        self.keep().read(Command::RNEDSI, 2, |w| InstructionResult::RNEDSIReturn(RNEDSIResult::from_words(&words(w))))
+    }
+
+    pub(crate) fn rddst(&self) -> Transaction {
+        self.keep().read(Command::RDDST, 5, |w| InstructionResult::RDDSTReturn(RDDSTResult::from_words(&words(w))))
+    }
+
+    pub(crate) fn rddpm(&self) -> Transaction {
+        self.keep().read(Command::RDDPM, 2, |w| InstructionResult::RDDPMReturn(RDDPMResult::from_words(&words(w))))
+    }
+
+    pub(crate) fn rddmadctl(&self) -> Transaction {
+        self.keep().read(Command::RDDMADCTL, 2, |w| InstructionResult::RDDMADCTLReturn(RDDMADCTLResult::from_words(&words(w))))
+    }
+
+    pub(crate) fn rddpixfmt(&self) -> Transaction {
+        self.keep().read(Command::RDDPIXFMT, 2, |w| InstructionResult::RDDPIXFMTReturn(RDDPIXFMTResult::from_words(&words(w))))
+    }
+
+    pub(crate) fn rddim(&self) -> Transaction {
+        self.keep().read(Command::RDDIM, 2, |w| InstructionResult::RDDIMReturn(RDDIMResult::from_words(&words(w))))
+    }
+
+    pub(crate) fn rddsm(&self) -> Transaction {
+        self.keep().read(Command::RDDSM, 2, |w| InstructionResult::RDDSMReturn(RDDSMResult::from_words(&words(w))))
+    }
+
+    pub(crate) fn rddsdr(&self) -> Transaction {
+        self.keep().read(Command::RDDSDR, 2, |w| InstructionResult::RDDSDRReturn(RDDSDRResult::from_words(&words(w))))
+    }
+
+    pub(crate) fn invoff(&self) -> Transaction {
+        self.keep().write(Command::INVOFF, &[])
+    }
+
+    pub(crate) fn invon(&self) -> Transaction {
+        self.keep().write(Command::INVON, &[])
+    }
+
+    pub(crate) fn dispoff(&self) -> Transaction {
+        self.with_power(PowerState { display: OnOff::OFF, ..self.power }).write(Command::DISPOFF, &[])
+    }
+
+    // DISPON, refused while the panel is asleep
+    pub(crate) fn dispon(&self) -> Result<Transaction, Rejected> {
+        if self.power.sleep == InOut::IN {
+            warn!("DISPON rejected while in sleep-in");
+            return Err(Rejected::InvalidState);
+        }
+        Ok(self.display_on())
+    }
+
+    // DISPON, for callers that have just sent SLPOUT
+    pub(crate) fn display_on(&self) -> Transaction {
+        self.with_power(PowerState { display: OnOff::ON, ..self.power }).write(Command::DISPON, &[])
+    }
+
+    // DISPOFF if needed, SLPIN, then the 120 ms required before SLPOUT
+    pub(crate) fn sleep_in(&self) -> Transaction {
+        let tx = self.with_power(PowerState { sleep: InOut::IN, display: OnOff::OFF, ..self.power });
+        let tx = if self.power.display == OnOff::ON {
+            tx.write(Command::DISPOFF, &[])
+        } else {
+            tx
+        };
+        tx.write(Command::SLPIN, &[]).wait(120)
+    }
+
+    // SLPOUT, then 120 ms for the supplies to settle
+    pub(crate) fn sleep_out(&self) -> Transaction {
+        self.with_power(PowerState { sleep: InOut::OUT, ..self.power }).write(Command::SLPOUT, &[]).wait(120)
+    }
+
+    pub(crate) fn ptlar(&self, start_row: u16, end_row: u16) -> Result<Transaction, Rejected> {
+        if start_row >= HEIGHT || end_row >= HEIGHT {
+            return Err(Rejected::InvalidParameter);
+        }
+        let [sr_h, sr_l] = start_row.to_be_bytes();
+        let [er_h, er_l] = end_row.to_be_bytes();
+        Ok(self.keep().write(Command::PTLAR, &[sr_h, sr_l, er_h, er_l]))
+    }
+
+    // PTLON, which also ends vertical scrolling
+    pub(crate) fn ptlon(&self) -> Transaction {
+        Transaction::new(Shadow {
+            power: PowerState { mode: DisplayMode::PARTIAL, ..self.power },
+            scroll: self.scroll.unscrolled(),
+            ..*self
+        })
+        .write(Command::PTLON, &[])
+    }
+
+    // NORON, which also ends vertical scrolling
+    pub(crate) fn noron(&self) -> Transaction {
+        Transaction::new(Shadow {
+            power: PowerState { mode: DisplayMode::NORMAL, ..self.power },
+            scroll: self.scroll.unscrolled(),
+            ..*self
+        })
+        .write(Command::NORON, &[])
+    }
+
+    pub(crate) fn idmon(&self) -> Transaction {
+        self.with_power(PowerState { idle: OnOff::ON, ..self.power }).write(Command::IDMON, &[])
+    }
+
+    pub(crate) fn idmoff(&self) -> Transaction {
+        self.with_power(PowerState { idle: OnOff::OFF, ..self.power }).write(Command::IDMOFF, &[])
+    }
+
+    pub(crate) fn frmctr2(&self, frame_rate: IdleFrameRate) -> Result<Transaction, Rejected> {
+        let bytes = frame_rate.to_bytes().ok_or(Rejected::InvalidParameter)?;
+        Ok(self.keep().extended(Command::FRMCTR2, &bytes))
+    }
+
+    // Interface pixel format (COLMOD)
+    pub(crate) fn colmod(&self, colmod: u8) -> Transaction {
+        self.keep().write(Command::PIXFMT, &[colmod])
+    }
+
+    // CASET/RASET, checked against the current logical size
+    pub(crate) fn set_window(&self, x0: u16, y0: u16, x1: u16, y1: u16) -> Result<Transaction, Rejected> {
+        let (w, h) = self.orientation.size();
+        if x0 > x1 || y0 > y1 || x1 >= w || y1 >= h {
+            return Err(Rejected::InvalidParameter);
+        }
+        Ok(self.window(x0, y0, x1, y1))
+    }
+
+    // CASET/RASET for a window known to be in range
+    pub(crate) fn window(&self, x0: u16, y0: u16, x1: u16, y1: u16) -> Transaction {
+        let [xs_h, xs_l] = x0.to_be_bytes();
+        let [xe_h, xe_l] = x1.to_be_bytes();
+        let [ys_h, ys_l] = y0.to_be_bytes();
+        let [ye_h, ye_l] = y1.to_be_bytes();
+        self.keep()
+            .write(Command::CASET, &[xs_h, xs_l, xe_h, xe_l])
+            .write(Command::RASET, &[ys_h, ys_l, ye_h, ye_l])
+    }
+
+    pub(crate) fn set_orientation(&self, rotation: Rotation, mirror: bool, rgb_order: RgbOrder) -> Transaction {
+        let orientation = Orientation::new(rotation, mirror, rgb_order);
+        Transaction::new(Shadow { orientation, ..*self }).write(Command::MADCTL, &[orientation.madctl()])
+    }
+
+    // The transaction `exec` runs for `command`
+    pub(crate) fn exec(&self, command: Command) -> Result<Transaction, Rejected> {
+        Ok(match command {
+            Command::NOP => self.nop(),
+            Command::SWRESET => self.swreset(),
+            Command::RDDID => self.rddid(),
+            Command::RNEDSI => self.rnedsi(),
+            Command::RDDST => self.rddst(),
+            Command::RDDPM => self.rddpm(),
+            Command::RDDMADCTL => self.rddmadctl(),
+            Command::RDDPIXFMT => self.rddpixfmt(),
+            Command::RDDIM => self.rddim(),
+            Command::RDDSM => self.rddsm(),
+            Command::RDDSDR => self.rddsdr(),
+            Command::INVOFF => self.invoff(),
+            Command::INVON => self.invon(),
+            Command::DISPOFF => self.dispoff(),
+            Command::DISPON => self.dispon()?,
+            Command::SLPIN => self.sleep_in(),
+            Command::SLPOUT => self.sleep_out(),
+            Command::PTLON => self.ptlon(),
+            Command::NORON => self.noron(),
+            Command::TEOFF => self.teoff(),
+            Command::IDMOFF => self.idmoff(),
+            Command::IDMON => self.idmon(),
+            Command::RDTESCAN => self.rdtescan(),
+            Command::RDDISBV => self.rddisbv(),
+            Command::RDCTRLD => self.rdctrld(),
+            Command::RDCABC => self.rdcabc(),
+            Command::RDCABCMB => self.rdcabcmb(),
+            _ => return Err(Rejected::UnsupportedCommand),
+        })
+    }
+}
@@ -0,0 +1,417 @@
+// Power-on configuration applied by `ST7796S::init`.
+use crate::command::RegisterWrite;
+use crate::instruction::Command;
+use crate::{PixelFormat, RgbOrder};
+
+/// Native width of the ST7796S in pixels, in its portrait scan direction.
+pub const WIDTH: u16 = 320;
+/// Native height of the ST7796S in pixels, in its portrait scan direction.
+pub const HEIGHT: u16 = 480;
+
+// MADCTL bits
+pub(crate) const MADCTL_MY: u8 = 0x80;
+pub(crate) const MADCTL_MX: u8 = 0x40;
+pub(crate) const MADCTL_MV: u8 = 0x20;
+pub(crate) const MADCTL_BGR: u8 = 0x08;
+
+/// Rotation of the picture relative to the panel's native portrait scan.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Rotation {
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+impl Rotation {
+    // MY/MX/MV bits producing this rotation
+    pub(crate) fn madctl(self) -> u8 {
+        match self {
+            Rotation::Deg0 => 0,
+            Rotation::Deg90 => MADCTL_MV | MADCTL_MX,
+            Rotation::Deg180 => MADCTL_MY | MADCTL_MX,
+            Rotation::Deg270 => MADCTL_MV | MADCTL_MY,
+        }
+    }
+
+    /// Logical (width, height) of the screen in this rotation.
+    pub fn size(self) -> (u16, u16) {
+        match self {
+            Rotation::Deg0 | Rotation::Deg180 => (WIDTH, HEIGHT),
+            Rotation::Deg90 | Rotation::Deg270 => (HEIGHT, WIDTH),
+        }
+    }
+}
+
+/// Memory access settings written to MADCTL.
+///
+/// The reset value is portrait, unmirrored, RGB order (MADCTL 0x00).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Orientation {
+    pub rotation: Rotation,
+    /// Mirror the picture horizontally
+    pub mirror: bool,
+    pub rgb_order: RgbOrder,
+}
+
+impl Orientation {
+    pub(crate) const RESET: Orientation = Orientation {
+        rotation: Rotation::Deg0,
+        mirror: false,
+        rgb_order: RgbOrder::RGB,
+    };
+
+    pub fn new(rotation: Rotation, mirror: bool, rgb_order: RgbOrder) -> Self {
+        Orientation {
+            rotation,
+            mirror,
+            rgb_order,
+        }
+    }
+
+    // MADCTL value for this rotation, mirroring and colour order
+    pub(crate) fn madctl(&self) -> u8 {
+        let mut madctl = self.rotation.madctl();
+        if self.mirror {
+            madctl ^= MADCTL_MX;
+        }
+        if let RgbOrder::BGR = self.rgb_order {
+            madctl |= MADCTL_BGR;
+        }
+        madctl
+    }
+
+    /// Logical (width, height) of the screen in this orientation.
+    pub fn size(&self) -> (u16, u16) {
+        self.rotation.size()
+    }
+}
+
+impl Default for Orientation {
+    fn default() -> Self {
+        Orientation::RESET
+    }
+}
+
+/// FRMCTR1 settings for normal mode (full colours).
+///
+/// The defaults are the panel's reset values, which give roughly 60 Hz.
+#[derive(Clone, Copy, Debug)]
+pub struct FrameRate {
+    /// Frame rate select FRS[3:0]
+    pub frs: u8,
+    /// Internal clock division DIVA[1:0]: fosc / 1, 2, 4 or 8
+    pub diva: u8,
+    /// Clocks per line RTNA[4:0]
+    pub rtna: u8,
+}
+
+impl Default for FrameRate {
+    fn default() -> Self {
+        FrameRate {
+            frs: 0x0A,
+            diva: 0,
+            rtna: 0x10,
+        }
+    }
+}
+
+impl FrameRate {
+    // FRMCTR1 parameter bytes, or `None` if a field doesn't fit its bits
+    pub(crate) fn to_bytes(self) -> Option<[u8; 2]> {
+        if self.frs > 0x0F || self.diva > 0x03 || self.rtna > 0x1F {
+            return None;
+        }
+        Some([(self.frs << 4) | self.diva, self.rtna])
+    }
+}
+
+/// FRMCTR2 settings for idle mode (8 colours).
+///
+/// Idle mode always runs the internal clock undivided; the default is the
+/// panel's reset value.
+#[derive(Clone, Copy, Debug)]
+pub struct IdleFrameRate {
+    /// Clocks per line RTNB[4:0]
+    pub rtnb: u8,
+}
+
+impl Default for IdleFrameRate {
+    fn default() -> Self {
+        IdleFrameRate { rtnb: 0x10 }
+    }
+}
+
+impl IdleFrameRate {
+    // FRMCTR2 parameter bytes, or `None` if RTNB doesn't fit its bits
+    pub(crate) fn to_bytes(self) -> Option<[u8; 2]> {
+        if self.rtnb > 0x1F {
+            return None;
+        }
+        Some([0x00, self.rtnb])
+    }
+}
+
+/// FRMCTR3 settings for partial mode (full colours).
+///
+/// Like idle mode, partial mode runs the internal clock undivided; the
+/// default is the panel's reset value.
+#[derive(Clone, Copy, Debug)]
+pub struct PartialFrameRate {
+    /// Clocks per line RTNC[4:0]
+    pub rtnc: u8,
+}
+
+impl Default for PartialFrameRate {
+    fn default() -> Self {
+        PartialFrameRate { rtnc: 0x10 }
+    }
+}
+
+impl PartialFrameRate {
+    // FRMCTR3 parameter bytes, or `None` if RTNC doesn't fit its bits
+    pub(crate) fn to_bytes(self) -> Option<[u8; 2]> {
+        if self.rtnc > 0x1F {
+            return None;
+        }
+        Some([0x00, self.rtnc])
+    }
+}
+
+/// Positive (PGC) and negative (NGC) gamma correction tables.
+///
+/// Unlike the ST7735/ST7789 family the ST7796S has no GAMSET command with
+/// predefined curves to pick from; the gamma curve is set only through these
+/// two tables.
+#[derive(Clone, Copy, Debug)]
+pub struct Gamma {
+    pub positive: [u8; 14],
+    pub negative: [u8; 14],
+}
+
+/// Panel-specific power, timing and gamma registers sent during `init`.
+///
+/// Every register is optional; `None` leaves the panel's reset value alone.
+/// Start from one of the built-in profiles and override single registers
+/// for a particular module:
+///
+/// ```ignore
+/// let profile = InitProfile { vcmpctr: Some(0x1C), ..InitProfile::SITRONIX };
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct InitProfile {
+    /// INVCTR: display inversion control
+    pub invctr: Option<u8>,
+    /// DFC: display function control
+    pub dfc: Option<[u8; 3]>,
+    /// DOCA: display output control adjust
+    pub doca: Option<[u8; 8]>,
+    /// PWCTR1: AVDD/AVCL/VGH/VGL
+    pub pwctr1: Option<[u8; 2]>,
+    /// PWCTR2: VAP/VAN
+    pub pwctr2: Option<u8>,
+    /// PWCTR3: source and gamma driving current in normal mode
+    pub pwctr3: Option<u8>,
+    /// VCMPCTR: VCOM voltage
+    pub vcmpctr: Option<u8>,
+    /// PGC/NGC gamma tables
+    pub gamma: Option<Gamma>,
+}
+
+impl InitProfile {
+    /// Send nothing beyond the basic configuration; the panel keeps its
+    /// reset/OTP values.
+    pub const NONE: InitProfile = InitProfile {
+        invctr: None,
+        dfc: None,
+        doca: None,
+        pwctr1: None,
+        pwctr2: None,
+        pwctr3: None,
+        vcmpctr: None,
+        gamma: None,
+    };
+
+    /// The register set from the Sitronix application note for 3.5" 320x480
+    /// ST7796S modules.
+    pub const SITRONIX: InitProfile = InitProfile {
+        invctr: Some(0x01),
+        dfc: Some([0x80, 0x02, 0x3B]),
+        doca: Some([0x40, 0x8A, 0x00, 0x00, 0x29, 0x19, 0xA5, 0x33]),
+        pwctr1: Some([0x80, 0x25]),
+        pwctr2: Some(0x06),
+        pwctr3: Some(0xA7),
+        vcmpctr: Some(0x18),
+        gamma: Some(Gamma {
+            positive: [0xF0, 0x09, 0x0B, 0x06, 0x04, 0x15, 0x2F, 0x54, 0x42, 0x3C, 0x17, 0x14, 0x18, 0x1B],
+            negative: [0xE0, 0x09, 0x0B, 0x06, 0x04, 0x03, 0x2B, 0x43, 0x42, 0x3B, 0x16, 0x14, 0x17, 0x1B],
+        }),
+    };
+}
+
+impl Default for InitProfile {
+    fn default() -> Self {
+        InitProfile::NONE
+    }
+}
+
+// Number of extended registers `init` may write
+pub(crate) const EXTENDED_WRITES: usize = 10;
+
+/// Settings applied by `ST7796S::init`.
+///
+/// Built with chained setters on top of `InitConfig::new()`, which matches
+/// what `init` used to hard-code: portrait, RGB565, RGB order, no inversion,
+/// the panel's own gamma and frame rate, GRAM cleared to black and the
+/// display switched on.
+#[derive(Clone, Copy, Debug)]
+pub struct InitConfig {
+    pub(crate) rotation: Rotation,
+    pub(crate) mirror: bool,
+    pub(crate) pixel_format: PixelFormat,
+    pub(crate) rgb_order: RgbOrder,
+    pub(crate) inversion: bool,
+    pub(crate) profile: InitProfile,
+    pub(crate) gamma: Option<Gamma>,
+    pub(crate) frame_rate: Option<FrameRate>,
+    pub(crate) fill: Option<u16>,
+    pub(crate) display_on: bool,
+}
+
+impl Default for InitConfig {
+    fn default() -> Self {
+        InitConfig {
+            rotation: Rotation::Deg0,
+            mirror: false,
+            pixel_format: PixelFormat::Bit16,
+            rgb_order: RgbOrder::RGB,
+            inversion: false,
+            profile: InitProfile::NONE,
+            gamma: None,
+            frame_rate: None,
+            fill: Some(0x0000),
+            display_on: true,
+        }
+    }
+}
+
+impl InitConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Picture rotation, written to MADCTL.
+    pub fn rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Mirror the picture horizontally.
+    pub fn mirror(mut self, mirror: bool) -> Self {
+        self.mirror = mirror;
+        self
+    }
+
+    /// Interface pixel format, written to COLMOD.
+    pub fn pixel_format(mut self, format: PixelFormat) -> Self {
+        self.pixel_format = format;
+        self
+    }
+
+    /// Order of the colour components on the panel.
+    pub fn rgb_order(mut self, order: RgbOrder) -> Self {
+        self.rgb_order = order;
+        self
+    }
+
+    /// Invert all colours (INVON). Most IPS modules need this.
+    pub fn inversion(mut self, on: bool) -> Self {
+        self.inversion = on;
+        self
+    }
+
+    /// Power, timing and gamma registers for the specific panel.
+    pub fn profile(mut self, profile: InitProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Replace the panel's gamma curves, overriding those of the profile.
+    ///
+    /// The ST7796S has no GAMSET, so the PGC/NGC tables are the only way to
+    /// change the curve.
+    pub fn gamma(mut self, gamma: Gamma) -> Self {
+        self.gamma = Some(gamma);
+        self
+    }
+
+    /// Replace the panel's normal-mode frame rate.
+    pub fn frame_rate(mut self, frame_rate: FrameRate) -> Self {
+        self.frame_rate = Some(frame_rate);
+        self
+    }
+
+    /// RGB565 colour to clear GRAM to, or `None` to leave it untouched.
+    pub fn fill(mut self, color: Option<u16>) -> Self {
+        self.fill = color;
+        self
+    }
+
+    /// Whether to send DISPON at the end of `init`.
+    pub fn display_on(mut self, on: bool) -> Self {
+        self.display_on = on;
+        self
+    }
+
+    // Writes to the extended command set in send order, or `None` if a
+    // setting is out of range
+    pub(crate) fn extended_writes(&self) -> Option<[Option<RegisterWrite>; EXTENDED_WRITES]> {
+        let p = &self.profile;
+        let frame_rate = match self.frame_rate {
+            Some(fr) => Some(fr.to_bytes()?),
+            None => None,
+        };
+        let gamma = self.gamma.or(p.gamma);
+        Some([
+            p.invctr.map(|v| RegisterWrite::new(Command::INVCTR, &[v])),
+            p.dfc.map(|v| RegisterWrite::new(Command::DFC, &v)),
+            p.doca.map(|v| RegisterWrite::new(Command::DOCA, &v)),
+            p.pwctr1.map(|v| RegisterWrite::new(Command::PWCTR1, &v)),
+            p.pwctr2.map(|v| RegisterWrite::new(Command::PWCTR2, &[v])),
+            p.pwctr3.map(|v| RegisterWrite::new(Command::PWCTR3, &[v])),
+            p.vcmpctr.map(|v| RegisterWrite::new(Command::VCMPCTR, &[v])),
+            frame_rate.map(|v| RegisterWrite::new(Command::FRMCTR1, &v)),
+            gamma.map(|g| RegisterWrite::new(Command::PGC, &g.positive)),
+            gamma.map(|g| RegisterWrite::new(Command::NGC, &g.negative)),
+        ])
+    }
+
+    // COLMOD value for the configured pixel format
+    pub(crate) fn colmod(&self) -> Option<u8> {
+        match self.pixel_format {
+            PixelFormat::Bit16 => Some(0x55),
+            PixelFormat::Bit18 => Some(0x66),
+            PixelFormat::Bit24 => Some(0x77),
+            PixelFormat::Undefined => None,
+        }
+    }
+
+    // `color` as it goes over the bus in the configured pixel format, and the
+    // number of bytes used
+    pub(crate) fn pixel_bytes(&self, color: u16) -> ([u8; 3], usize) {
+        match self.pixel_format {
+            PixelFormat::Bit16 => {
+                let [h, l] = color.to_be_bytes();
+                ([h, l, 0], 2)
+            }
+            _ => {
+                let r = ((color >> 11) & 0x1f) as u8;
+                let g = ((color >> 5) & 0x3f) as u8;
+                let b = (color & 0x1f) as u8;
+                ([r << 3, g << 2, b << 3], 3)
+            }
+        }
+    }
+}
@@ -0,0 +1,25 @@
+// Driver error type
+
+/// Errors reported by the ST7796S driver.
+///
+/// `BusE` is the error type of the display interface's bus and `PinE` the
+/// error type shared by its control pins and the RST pin.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<BusE, PinE> {
+    /// The bus (SPI, parallel, ...) reported an error
+    Bus(BusE),
+    /// Driving one of the control pins failed
+    Pin(PinE),
+    /// A parameter was outside the range accepted by the panel
+    InvalidParameter,
+    /// The command isn't allowed in the panel's current power state, or a DMA
+    /// transfer still owns the bus
+    InvalidState,
+    /// `exec` was asked to run a command the driver doesn't implement
+    UnsupportedCommand,
+    /// The loopback test read back different bytes than it sent
+    LoopbackMismatch,
+    /// The TE pin didn't produce an edge within `tearing::TE_TIMEOUT_US`
+    Timeout,
+}
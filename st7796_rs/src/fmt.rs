@@ -0,0 +1,41 @@
+// Logging shims.
+//
+// The driver traces command bytes, parameters, read results and waits through
+// these macros. With the `log` or `defmt` feature they forward to that crate,
+// so the level filter is whatever the application's logger is set up with;
+// with neither they compile to nothing. If both are enabled, for example by
+// two crates in the same dependency graph, `defmt` wins and `log` stays
+// silent. Format strings must stay within the subset both crates understand
+// (`{}`, `{:?}`, `{:#x}`).
+#![allow(unused_macros)]
+
+macro_rules! log_at {
+    ($level:ident, $s:literal $(, $x:expr)* $(,)?) => {
+        {
+            #[cfg(all(feature = "log", not(feature = "defmt")))]
+            ::log::$level!($s $(, $x)*);
+            #[cfg(feature = "defmt")]
+            ::defmt::$level!($s $(, $x)*);
+            #[cfg(not(any(feature = "log", feature = "defmt")))]
+            {
+                $( let _ = & $x; )*
+            }
+        }
+    };
+}
+
+macro_rules! trace {
+    ($($arg:tt)*) => { log_at!(trace, $($arg)*) };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => { log_at!(debug, $($arg)*) };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => { log_at!(info, $($arg)*) };
+}
+
+macro_rules! warn {
+    ($($arg:tt)*) => { log_at!(warn, $($arg)*) };
+}
@@ -0,0 +1,316 @@
+// Power-on sequence.
+//
+// `InitSteps` spells out the datasheet power-on order once, as reset pin
+// levels, transactions and the GRAM clear, each with the wait that follows
+// it. The blocking and async `init` run the steps back to back.
+//
+// `ST7796S::init` spends over 400 ms in `DelayNs` waiting out reset and sleep
+// timings. `InitSequence` runs the same steps, but every wait is a comparison
+// against a caller-supplied millisecond timestamp, so a superloop or RTIC task
+// can keep servicing other work between calls to `ST7796S::poll_init`. It
+// never touches the driver's delay provider, so `T` need not implement
+// `DelayNs` at all.
+use core::task::Poll;
+
+use embedded_hal::digital::OutputPin;
+
+use crate::command::{Flood, RegisterWrite, Shadow, Transaction};
+use crate::config::EXTENDED_WRITES;
+use crate::{DisplayInterface, Error, InitConfig, ST7796S};
+
+// One step of the power-on sequence
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum InitStep {
+    // Drive RST high or low, then wait; skipped without a reset pin
+    Reset { high: bool, wait_ms: u32 },
+    // Send a transaction, then wait out its `wait_ms`
+    Send(Transaction),
+    // Stream the fill colour into the window opened by the previous step
+    Fill(FillArea),
+}
+
+// The whole screen in one colour, as cleared by `init`
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct FillArea {
+    pixel: [u8; 3],
+    len: usize,
+    width: u16,
+    height: u16,
+}
+
+impl FillArea {
+    pub(crate) fn height(&self) -> u16 {
+        self.height
+    }
+
+    // `rows` full rows of the fill colour
+    pub(crate) fn flood(&self, rows: u16) -> Flood {
+        Flood::new(&self.pixel[..self.len], self.width as u32 * rows as u32)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Phase {
+    ResetHigh,
+    ResetLow,
+    ResetRelease,
+    SoftReset,
+    SleepOut,
+    PixelFormat,
+    Orientation,
+    Unlock,
+    Extended(usize),
+    Lock,
+    Inversion,
+    Window,
+    Fill,
+    DisplayOn,
+    Done,
+}
+
+// The power-on steps for one `InitConfig`, in datasheet order
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct InitSteps {
+    config: InitConfig,
+    colmod: u8,
+    extended: [Option<RegisterWrite>; EXTENDED_WRITES],
+    shadow: Shadow,
+    phase: Phase,
+}
+
+impl InitSteps {
+    // `None` if `config` is out of range
+    pub(crate) fn new(config: &InitConfig) -> Option<Self> {
+        let (colmod, extended) = match (config.colmod(), config.extended_writes()) {
+            (Some(colmod), Some(extended)) => (colmod, extended),
+            _ => {
+                warn!("init: configuration out of range");
+                return None;
+            }
+        };
+        Some(InitSteps {
+            config: *config,
+            colmod,
+            extended,
+            shadow: Shadow::RESET,
+            phase: Phase::ResetHigh,
+        })
+    }
+
+    // Queue `tx`, tracking the state it leaves the panel in
+    fn send(&mut self, tx: Transaction) -> Option<InitStep> {
+        self.shadow = tx.next();
+        Some(InitStep::Send(tx))
+    }
+}
+
+impl Iterator for InitSteps {
+    type Item = InitStep;
+
+    fn next(&mut self) -> Option<InitStep> {
+        loop {
+            let config = self.config;
+            let (step, next) = match self.phase {
+                Phase::ResetHigh => {
+                    info!("init: reset");
+                    (Some(InitStep::Reset { high: true, wait_ms: 50 }), Phase::ResetLow)
+                }
+                Phase::ResetLow => (Some(InitStep::Reset { high: false, wait_ms: 50 }), Phase::ResetRelease),
+                Phase::ResetRelease => (Some(InitStep::Reset { high: true, wait_ms: 120 }), Phase::SoftReset),
+                Phase::SoftReset => (self.send(self.shadow.swreset()), Phase::SleepOut),
+                Phase::SleepOut => (self.send(self.shadow.sleep_out()), Phase::PixelFormat),
+                Phase::PixelFormat => {
+                    info!("init: configure");
+                    (self.send(self.shadow.colmod(self.colmod)), Phase::Orientation)
+                }
+                Phase::Orientation => {
+                    let tx = self.shadow.set_orientation(config.rotation, config.mirror, config.rgb_order);
+                    (self.send(tx), Phase::Unlock)
+                }
+                // Panel power, timing and gamma registers live in the
+                // extended command set
+                Phase::Unlock if self.extended.iter().any(Option::is_some) => {
+                    (self.send(self.shadow.keep().unlock()), Phase::Extended(0))
+                }
+                Phase::Unlock => (None, Phase::Inversion),
+                Phase::Extended(i) => match self.extended.get(i) {
+                    Some(Some(reg)) => (self.send(self.shadow.keep().register(*reg)), Phase::Extended(i + 1)),
+                    Some(None) => (None, Phase::Extended(i + 1)),
+                    None => (None, Phase::Lock),
+                },
+                Phase::Lock => (self.send(self.shadow.keep().lock()), Phase::Inversion),
+                Phase::Inversion if config.inversion => (self.send(self.shadow.invon()), Phase::Window),
+                Phase::Inversion => (self.send(self.shadow.invoff()), Phase::Window),
+                // Clear GRAM while the display is still off so the power-on
+                // garbage never shows
+                Phase::Window if config.fill.is_some() => {
+                    let (w, h) = config.rotation.size();
+                    (self.send(self.shadow.window(0, 0, w - 1, h - 1).ramwr()), Phase::Fill)
+                }
+                Phase::Window => (None, Phase::DisplayOn),
+                Phase::Fill => {
+                    let (width, height) = config.rotation.size();
+                    let (pixel, len) = config.pixel_bytes(config.fill.unwrap_or(0));
+                    (Some(InitStep::Fill(FillArea { pixel, len, width, height })), Phase::DisplayOn)
+                }
+                // SLPOUT went out above, so DISPON is always allowed here
+                Phase::DisplayOn if config.display_on => (self.send(self.shadow.display_on()), Phase::Done),
+                Phase::DisplayOn => (None, Phase::Done),
+                Phase::Done => return None,
+            };
+            self.phase = next;
+            if step.is_some() {
+                return step;
+            }
+        }
+    }
+}
+
+/// Progress of a poll-driven `init`, advanced by `ST7796S::poll_init`.
+///
+/// Timestamps are milliseconds from any monotonic clock; wrap-around of the
+/// `u32` counter is handled.
+#[derive(Clone, Copy, Debug)]
+pub struct InitSequence {
+    // `None` if the configuration is out of range
+    steps: Option<InitSteps>,
+    // Step that failed on the bus, to retry on the next call
+    pending: Option<InitStep>,
+    // Rows of the GRAM clear still to write
+    fill: Option<(FillArea, u16)>,
+    since: u32,
+    wait_ms: u32,
+    done: bool,
+}
+
+impl InitSequence {
+    pub fn new(config: InitConfig) -> Self {
+        InitSequence {
+            steps: InitSteps::new(&config),
+            pending: None,
+            fill: None,
+            since: 0,
+            wait_ms: 0,
+            done: false,
+        }
+    }
+
+    /// Whether the sequence has run to completion.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    // Start a wait of `ms` at `now`
+    fn wait(&mut self, now: u32, ms: u32) {
+        self.since = now;
+        self.wait_ms = ms;
+    }
+
+    // Whether the current wait is over at `now`
+    fn elapsed(&self, now: u32) -> bool {
+        now.wrapping_sub(self.since) >= self.wait_ms
+    }
+}
+
+impl<DI, RST, T, TE> ST7796S<DI, RST, T, TE>
+where
+    DI: DisplayInterface,
+    RST: OutputPin<Error = DI::PinError>,
+{
+    /// Advance `seq` as far as it can go at time `now` (in milliseconds).
+    ///
+    /// Returns `Poll::Pending` while a datasheet wait is still running or GRAM
+    /// is still being cleared, and `Poll::Ready(())` once the panel is fully
+    /// configured. Clearing GRAM writes one row per call, so no single call
+    /// holds the bus for long.
+    pub fn poll_init(&mut self, seq: &mut InitSequence, now: u32) -> Result<Poll<()>, Error<DI::BusError, DI::PinError>> {
+        loop {
+            if !seq.elapsed(now) {
+                return Ok(Poll::Pending);
+            }
+            if let Some((area, rows)) = seq.fill {
+                self.flood(area.flood(1))?;
+                seq.fill = if rows > 1 { Some((area, rows - 1)) } else { None };
+                return Ok(Poll::Pending);
+            }
+            let step = match (seq.pending.take(), seq.steps.as_mut()) {
+                (Some(step), _) => Some(step),
+                (None, Some(steps)) => steps.next(),
+                (None, None) => return Err(Error::InvalidParameter),
+            };
+            let res = match step {
+                Some(InitStep::Reset { high, wait_ms }) => self.reset_pin(high).map(|present| {
+                    if present {
+                        seq.wait(now, wait_ms);
+                    }
+                }),
+                Some(InitStep::Send(tx)) => self.send(tx).map(|_| seq.wait(now, tx.wait_ms())),
+                Some(InitStep::Fill(area)) => {
+                    seq.fill = Some((area, area.height()));
+                    Ok(())
+                }
+                None => {
+                    if !seq.done {
+                        info!("init: done");
+                        seq.done = true;
+                    }
+                    return Ok(Poll::Ready(()));
+                }
+            };
+            if let Err(e) = res {
+                seq.pending = step;
+                return Err(e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::config::FrameRate;
+    use crate::mock::{self, Op};
+
+    #[test]
+    fn poll_init_waits_across_clock_wrap() {
+        let (mut display, log) = mock::display();
+        let mut seq = InitSequence::new(InitConfig::new());
+        let start = u32::MAX - 20;
+
+        assert_eq!(display.poll_init(&mut seq, start).unwrap(), Poll::Pending);
+        assert_eq!(log.ops(), [Op::Reset(true)]);
+        // The 50 ms after the first reset edge ends past the wrap
+        assert_eq!(display.poll_init(&mut seq, start.wrapping_add(49)).unwrap(), Poll::Pending);
+        assert_eq!(log.ops(), [Op::Reset(true)]);
+        assert_eq!(display.poll_init(&mut seq, start.wrapping_add(50)).unwrap(), Poll::Pending);
+        assert_eq!(log.ops(), [Op::Reset(true), Op::Reset(false)]);
+
+        let mut now = start.wrapping_add(50);
+        while display.poll_init(&mut seq, now).unwrap() == Poll::Pending {
+            now = now.wrapping_add(1);
+            assert!(now.wrapping_sub(start) < 10_000, "init never finished");
+        }
+        assert!(seq.is_done());
+        // Reset and SWRESET/SLPOUT waits: 50 + 50 + 120 + 120 + 120 ms
+        assert!(now.wrapping_sub(start) >= 460);
+
+        // Same bus traffic as the blocking init, without touching the timer
+        let (mut blocking, reference) = mock::display();
+        blocking.init(&InitConfig::new()).unwrap();
+        let expected: Vec<Op> = reference.ops().into_iter().filter(|op| !matches!(op, Op::DelayMs(_))).collect();
+        assert_eq!(log.ops(), expected);
+    }
+
+    #[test]
+    fn poll_init_rejects_out_of_range_config() {
+        let (mut display, log) = mock::display();
+        let config = InitConfig::new().frame_rate(FrameRate { frs: 0x10, diva: 0, rtna: 0x10 });
+        let mut seq = InitSequence::new(config);
+        assert_eq!(display.poll_init(&mut seq, 0), Err(Error::InvalidParameter));
+        assert_eq!(log.ops(), []);
+    }
+}
@@ -93,9 +93,9 @@ pub enum Command {
     SPIRC         = 0xFB,   // SPI read control
 }
 
-impl Into<Command> for u8 {
-    fn into(self) -> Command {
-        match self {
+impl From<u8> for Command {
+    fn from(cmd: u8) -> Command {
+        match cmd {
             0x00 => Command::NOP,
             0x01 => Command::SWRESET,
             0x04 => Command::RDDID,
@@ -174,9 +174,9 @@ impl Into<Command> for u8 {
     }
 }
 
-impl Into<u8> for Command {
-    fn into(self) -> u8 {
-        match self {
+impl From<Command> for u8 {
+    fn from(cmd: Command) -> u8 {
+        match cmd {
             Command::NOP => 0x00,
             Command::SWRESET => 0x01,
             Command::RDDID => 0x04,
@@ -0,0 +1,420 @@
+// Transport layer between the ST7796S command set and the physical bus.
+use core::marker::PhantomData;
+
+use embedded_hal::digital::{self, OutputPin};
+use embedded_hal::spi::SpiBus;
+
+use crate::Error;
+
+// Size of the stack buffer used to batch pixels into `write_data` calls
+const PIXEL_CHUNK: usize = 64;
+
+// Number of 9-bit words batched per bus write in 3-wire mode
+const WORD_CHUNK: usize = 32;
+
+// Ninth bit of a 3-wire frame, set for parameters/pixels and clear for commands
+const DC_BIT: u16 = 0x100;
+
+/// A bus the ST7796S command layer can talk over.
+///
+/// Implementations own whatever select/data-command signalling the bus needs,
+/// so `ST7796S` only ever deals in command bytes, parameter bytes and pixels.
+pub trait DisplayInterface {
+    /// Error type of the underlying bus
+    type BusError;
+    /// Error type of the control pins (CS, DC, RST, ...)
+    type PinError;
+
+    /// Send a single command byte.
+    fn write_command(&mut self, cmd: u8) -> Result<(), Error<Self::BusError, Self::PinError>>;
+
+    /// Send parameter or pixel bytes following a command.
+    fn write_data(&mut self, data: &[u8]) -> Result<(), Error<Self::BusError, Self::PinError>>;
+
+    /// Send a read command and fill `data` with the panel's reply.
+    ///
+    /// The first byte of the reply is the dummy clock the panel inserts before
+    /// read data, exactly as it comes off the bus.
+    fn read(&mut self, cmd: u8, data: &mut [u8]) -> Result<(), Error<Self::BusError, Self::PinError>>;
+
+    /// Stream RGB565 pixels after a RAMWR/RAMWRC has been sent.
+    fn write_pixels<I>(&mut self, pixels: I) -> Result<(), Error<Self::BusError, Self::PinError>>
+    where
+        I: IntoIterator<Item = u16>,
+    {
+        let mut batch = PixelBatch::new();
+        for px in pixels {
+            if let Some(chunk) = batch.push(px) {
+                self.write_data(chunk)?;
+            }
+        }
+        match batch.rest() {
+            Some(chunk) => self.write_data(chunk),
+            None => Ok(()),
+        }
+    }
+}
+
+// RGB565 pixels packed big-endian into `PIXEL_CHUNK`-sized writes
+pub(crate) struct PixelBatch {
+    chunk: [u8; PIXEL_CHUNK],
+    len: usize,
+}
+
+impl PixelBatch {
+    pub(crate) fn new() -> Self {
+        PixelBatch {
+            chunk: [0; PIXEL_CHUNK],
+            len: 0,
+        }
+    }
+
+    // Add `px`, handing back the chunk once it is full
+    pub(crate) fn push(&mut self, px: u16) -> Option<&[u8]> {
+        self.chunk[self.len..self.len + 2].copy_from_slice(&px.to_be_bytes());
+        self.len += 2;
+        if self.len < PIXEL_CHUNK {
+            return None;
+        }
+        self.len = 0;
+        Some(&self.chunk)
+    }
+
+    // The partly filled chunk left over once the pixels run out
+    pub(crate) fn rest(&self) -> Option<&[u8]> {
+        if self.len == 0 {
+            return None;
+        }
+        Some(&self.chunk[..self.len])
+    }
+}
+
+/// Stand-in for a control pin that isn't wired to the MCU.
+///
+/// Use it for CS tied to ground or RESET tied to the MCU reset line. Driving
+/// it does nothing and never fails; `E` only has to match the error type of
+/// the pins that are connected.
+pub struct NoPin<E>(PhantomData<E>);
+
+impl<E> NoPin<E> {
+    pub fn new() -> Self {
+        NoPin(PhantomData)
+    }
+}
+
+impl<E> Default for NoPin<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E: digital::Error> digital::ErrorType for NoPin<E> {
+    type Error = E;
+}
+
+impl<E: digital::Error> OutputPin for NoPin<E> {
+    fn set_low(&mut self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), E> {
+        Ok(())
+    }
+}
+
+/// 4-wire SPI: an `SpiBus` plus separate CS and DC pins.
+pub struct SpiInterface<SPI, CS, DC>
+where
+    SPI: SpiBus,
+    CS: OutputPin,
+    DC: OutputPin<Error = CS::Error>,
+{
+    pub(crate) spi: SPI,
+    pub(crate) cs: CS,
+    pub(crate) dc: DC,
+}
+
+impl<SPI, CS, DC> SpiInterface<SPI, CS, DC>
+where
+    SPI: SpiBus,
+    CS: OutputPin,
+    DC: OutputPin<Error = CS::Error>,
+{
+    pub fn new(spi: SPI, cs: CS, dc: DC) -> Self {
+        SpiInterface { spi, cs, dc }
+    }
+
+    /// Return the bus and pins.
+    pub fn release(self) -> (SPI, CS, DC) {
+        (self.spi, self.cs, self.dc)
+    }
+
+    #[cold]
+    // Loop back test. Kind of doesn't work.
+    pub fn loopback_test(&mut self) -> Result<(), Error<SPI::Error, CS::Error>> {
+        let tx_buffer: [u8; 4]  = [0x00, 0xAA, 0xFF, 0x55];
+        let mut rx_buffer: [u8; 4] = [0u8; 4];
+        self.spi.transfer(&mut rx_buffer, &tx_buffer).map_err(Error::Bus)?;
+        if tx_buffer == rx_buffer {
+            Ok(())
+        } else {
+            Err(Error::LoopbackMismatch)
+        }
+    }
+}
+
+impl<SPI, DC> SpiInterface<SPI, NoPin<DC::Error>, DC>
+where
+    SPI: SpiBus,
+    DC: OutputPin,
+{
+    /// For modules whose CS is tied low: the panel stays selected and only
+    /// DC frames commands and data.
+    pub fn new_without_cs(spi: SPI, dc: DC) -> Self {
+        SpiInterface { spi, cs: NoPin::new(), dc }
+    }
+}
+
+impl<SPI, CS, DC> DisplayInterface for SpiInterface<SPI, CS, DC>
+where
+    SPI: SpiBus,
+    CS: OutputPin,
+    DC: OutputPin<Error = CS::Error>,
+{
+    type BusError = SPI::Error;
+    type PinError = CS::Error;
+
+    fn write_command(&mut self, cmd: u8) -> Result<(), Error<SPI::Error, CS::Error>> {
+        self.cs.set_low().map_err(Error::Pin)?;
+        self.dc.set_low().map_err(Error::Pin)?;
+        let res = self.spi.write(&[cmd]).map_err(Error::Bus);
+        self.cs.set_high().map_err(Error::Pin)?;
+        res
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> Result<(), Error<SPI::Error, CS::Error>> {
+        self.cs.set_low().map_err(Error::Pin)?;
+        self.dc.set_high().map_err(Error::Pin)?;
+        let res = self.spi.write(data).map_err(Error::Bus);
+        self.cs.set_high().map_err(Error::Pin)?;
+        res
+    }
+
+    fn read(&mut self, cmd: u8, data: &mut [u8]) -> Result<(), Error<SPI::Error, CS::Error>> {
+        // The panel aborts a read when CS rises, so keep it low from the
+        // command byte through the last data byte.
+        self.cs.set_low().map_err(Error::Pin)?;
+        self.dc.set_low().map_err(Error::Pin)?;
+        let mut res = self.spi.write(&[cmd]).map_err(Error::Bus);
+        if res.is_ok() {
+            self.dc.set_high().map_err(Error::Pin)?;
+            res = self.spi.read(data).map_err(Error::Bus);
+        }
+        self.cs.set_high().map_err(Error::Pin)?;
+        res
+    }
+}
+
+/// 3-wire serial: 9-bit SPI frames where the first bit carries D/C.
+///
+/// For modules without a DC line broken out. `SPI` must be configured for
+/// 9-bit words (for example an RP2350 `Spi<_, _, _, 9>`) and, for reads, have
+/// MISO wired to the panel's SDA/SDO.
+pub struct Spi3WireInterface<SPI, CS>
+where
+    SPI: SpiBus<u16>,
+    CS: OutputPin,
+{
+    pub(crate) spi: SPI,
+    pub(crate) cs: CS,
+}
+
+impl<SPI, CS> Spi3WireInterface<SPI, CS>
+where
+    SPI: SpiBus<u16>,
+    CS: OutputPin,
+{
+    pub fn new(spi: SPI, cs: CS) -> Self {
+        Spi3WireInterface { spi, cs }
+    }
+
+    /// Return the bus and CS pin.
+    pub fn release(self) -> (SPI, CS) {
+        (self.spi, self.cs)
+    }
+
+    // Send `data` as 9-bit words with the D/C bit set as requested
+    fn write_words(&mut self, data: &[u8], dc: u16) -> Result<(), Error<SPI::Error, CS::Error>> {
+        let mut words = [0u16; WORD_CHUNK];
+        for chunk in data.chunks(WORD_CHUNK) {
+            for (w, &b) in words.iter_mut().zip(chunk) {
+                *w = dc | b as u16;
+            }
+            self.spi.write(&words[..chunk.len()]).map_err(Error::Bus)?;
+        }
+        Ok(())
+    }
+
+    // Clock out enough 9-bit words to cover `data` and unpack the bitstream
+    // into bytes, dropping `skip` leading dummy bits.
+    fn read_bits(&mut self, data: &mut [u8], skip: u32) -> Result<(), Error<SPI::Error, CS::Error>> {
+        let mut acc: u32 = 0;
+        let mut bits: u32 = 0;
+        let mut skip = skip;
+        let mut filled = 0;
+        while filled < data.len() {
+            let mut word = [0u16; 1];
+            self.spi.read(&mut word).map_err(Error::Bus)?;
+            acc = (acc << 9) | (word[0] as u32 & 0x1ff);
+            bits += 9;
+            if skip > 0 {
+                let n = skip.min(bits);
+                bits -= n;
+                skip -= n;
+                acc &= (1 << bits) - 1;
+            }
+            while bits >= 8 && filled < data.len() {
+                data[filled] = (acc >> (bits - 8)) as u8;
+                bits -= 8;
+                acc &= (1 << bits) - 1;
+                filled += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<SPI, CS> DisplayInterface for Spi3WireInterface<SPI, CS>
+where
+    SPI: SpiBus<u16>,
+    CS: OutputPin,
+{
+    type BusError = SPI::Error;
+    type PinError = CS::Error;
+
+    fn write_command(&mut self, cmd: u8) -> Result<(), Error<SPI::Error, CS::Error>> {
+        self.cs.set_low().map_err(Error::Pin)?;
+        let res = self.write_words(&[cmd], 0);
+        self.cs.set_high().map_err(Error::Pin)?;
+        res
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> Result<(), Error<SPI::Error, CS::Error>> {
+        self.cs.set_low().map_err(Error::Pin)?;
+        let res = self.write_words(data, DC_BIT);
+        self.cs.set_high().map_err(Error::Pin)?;
+        res
+    }
+
+    fn read(&mut self, cmd: u8, data: &mut [u8]) -> Result<(), Error<SPI::Error, CS::Error>> {
+        // The serial reply isn't byte aligned: the panel inserts one dummy
+        // clock before multi-byte replies (RDDID, RDDST, ...) and none before
+        // single-byte ones. Keep the same layout as the 4-wire interface, with
+        // a zeroed dummy byte in front of the payload.
+        let (dummy, payload) = match data.split_first_mut() {
+            Some(split) => split,
+            None => return Ok(()),
+        };
+        *dummy = 0;
+        let skip = if payload.len() > 1 { 1 } else { 0 };
+
+        self.cs.set_low().map_err(Error::Pin)?;
+        let mut res = self.write_words(&[cmd], 0);
+        if res.is_ok() {
+            res = self.read_bits(payload, skip);
+        }
+        self.cs.set_high().map_err(Error::Pin)?;
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::convert::Infallible;
+    use std::collections::VecDeque;
+    use std::vec::Vec;
+
+    use embedded_hal::spi::ErrorType;
+
+    use super::*;
+
+    // 9-bit bus that records writes and clocks in queued words on reads
+    #[derive(Default)]
+    struct WordBus {
+        written: Vec<u16>,
+        rx: VecDeque<u16>,
+    }
+
+    impl ErrorType for WordBus {
+        type Error = Infallible;
+    }
+
+    impl SpiBus<u16> for WordBus {
+        fn read(&mut self, words: &mut [u16]) -> Result<(), Infallible> {
+            for w in words {
+                *w = self.rx.pop_front().unwrap_or(0x1ff);
+            }
+            Ok(())
+        }
+
+        fn write(&mut self, words: &[u16]) -> Result<(), Infallible> {
+            self.written.extend_from_slice(words);
+            Ok(())
+        }
+
+        fn transfer(&mut self, read: &mut [u16], write: &[u16]) -> Result<(), Infallible> {
+            self.write(write)?;
+            self.read(read)
+        }
+
+        fn transfer_in_place(&mut self, words: &mut [u16]) -> Result<(), Infallible> {
+            self.read(words)
+        }
+
+        fn flush(&mut self) -> Result<(), Infallible> {
+            Ok(())
+        }
+    }
+
+    // Split the `n` most significant bits of `bits` into 9-bit words, padding
+    // the last word with ones
+    fn words(bits: u64, n: u32) -> VecDeque<u16> {
+        let pad = (9 - n % 9) % 9;
+        let stream = (bits << pad) | ((1 << pad) - 1);
+        let count = (n + pad) / 9;
+        (0..count).rev().map(|i| ((stream >> (i * 9)) & 0x1ff) as u16).collect()
+    }
+
+    fn interface(rx: VecDeque<u16>) -> Spi3WireInterface<WordBus, NoPin<Infallible>> {
+        Spi3WireInterface::new(WordBus { written: Vec::new(), rx }, NoPin::new())
+    }
+
+    #[test]
+    fn multi_byte_reads_drop_the_dummy_bit() {
+        // A set dummy bit ahead of the three RDDID bytes
+        let mut bus = interface(words(0x1_76_80_96, 25));
+        let mut data = [0xAA; 4];
+        bus.read(0x04, &mut data).unwrap();
+        assert_eq!(bus.spi.written, [0x004]);
+        assert_eq!(data, [0x00, 0x76, 0x80, 0x96]);
+    }
+
+    #[test]
+    fn single_byte_reads_have_no_dummy_bit() {
+        let mut bus = interface(words(0x9C, 8));
+        let mut data = [0xAA; 2];
+        bus.read(0x0A, &mut data).unwrap();
+        assert_eq!(bus.spi.written, [0x00A]);
+        assert_eq!(data, [0x00, 0x9C]);
+    }
+
+    #[test]
+    fn commands_and_data_carry_the_dc_bit() {
+        let mut bus = interface(VecDeque::new());
+        bus.write_command(0x2A).unwrap();
+        bus.write_data(&[0x00, 0x3F]).unwrap();
+        assert_eq!(bus.spi.written, [0x02A, 0x100, 0x13F]);
+    }
+}
@@ -2,100 +2,149 @@
 // use core::cell::RefCell;
 
 /**/
-use embedded_hal::spi::{
-    SpiBus,
-    // MODE_0
-};
 use embedded_hal::digital::OutputPin;
+use embedded_hal::delay::DelayNs;
 
-// use fugit::RateExtU32;
-use rp235x_hal::{
-    self as hal, 
-    // gpio::{FunctionSpi, Pins}, 
-    pac, 
-    spi::{
-        // Disabled, 
-        Enabled, 
-        // Spi, 
-        // SpiDevice, 
-        // State, 
-        ValidSpiPinout
-    }, 
-    // Sio,
-    // Timer,
-};
-
-// use embedded_hal::delay::DelayNs;
-
-// Some things we need
-// use embedded_hal_0_2::prelude::*;
-use hal::clocks::Clock;
-use hal::fugit::RateExtU32;
+#[macro_use]
+mod fmt;
 
+pub mod brightness;
+mod command;
+pub mod config;
+pub mod error;
+pub mod init;
 pub mod instruction;
+pub mod interface;
+pub mod power;
+pub mod scroll;
+pub mod tearing;
+pub mod timing;
 
-use crate::instruction::Command;
+#[cfg(feature = "async")]
+pub mod asynch;
+
+#[cfg(test)]
+mod mock;
+
+#[cfg(feature = "rp235x")]
+pub mod rp235x;
 
-/// External high-speed crystal on the Raspberry Pi Pico 2 board is 12 MHz.
-/// Adjust if your board has a different frequency
-// const XTAL_FREQ_HZ: u32 = 12_000_000u32;
+#[cfg(feature = "rp235x")]
+pub mod pio8080;
+
+use crate::command::{Flood, Shadow, Transaction, REPLY_MAX};
+use crate::init::{InitStep, InitSteps};
+use crate::instruction::Command;
+pub use crate::brightness::{CabcMode, DisplayControl, PwmBacklight};
+pub use crate::config::{FrameRate, Gamma, IdleFrameRate, InitConfig, InitProfile, Orientation, PartialFrameRate, Rotation};
+pub use crate::error::Error;
+pub use crate::init::InitSequence;
+pub use crate::scroll::ScrollArea;
+pub use crate::timing::{BlankingPorch, ColorExpansion, DisplayFunction, EntryMode, GateOutput, InversionMode, NonDisplayLevel};
+pub use crate::power::{DriveCurrent, PowerControl1};
+pub use crate::interface::{DisplayInterface, NoPin, Spi3WireInterface, SpiInterface};
 
-pub struct ST7796S<P, CS, DC, RST, T>
+/// ST7796S driver over any `DisplayInterface`.
+///
+/// For the usual 4-wire SPI wiring wrap a configured `SpiBus` and the CS/DC
+/// pins in a `SpiInterface`. On the RP2350 see `ST7796S::new_rp235x`.
+///
+/// `T` can be a `&mut` borrow of the application's delay provider, so the
+/// timer stays usable elsewhere while the driver isn't running. `release`
+/// hands every owned resource back.
+///
+/// `TE` is the optional tearing effect input, attached with `with_te_pin`;
+/// without one it stays a `NoPin`.
+pub struct ST7796S<DI, RST, T, TE = NoPin<<DI as DisplayInterface>::PinError>>
 where 
-    P: ValidSpiPinout<pac::SPI0>,
-    CS: OutputPin,
-    DC: OutputPin,
-    RST: OutputPin,
-    T: embedded_hal::delay::DelayNs
+    DI: DisplayInterface,
+    RST: OutputPin<Error = DI::PinError>,
 {
-    interface: hal::spi::Spi<Enabled, pac::SPI0, P>,
-    cs: CS,
-    dc: DC,
-    rst: RST,
+    interface: DI,
+    rst: Option<RST>,
     timer: T,
+    te: TE,
+    shadow: Shadow,
 }
 
-#[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct RDDIDResult {
-    lcd_manufacturer_id: u8,
-    lcd_driver_version: u8,
-    lcd_driver_id: u8,
+    pub lcd_manufacturer_id: u8,
+    pub lcd_driver_version: u8,
+    pub lcd_driver_id: u8,
 }
 
-#[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct RNEDSIResult {
-    num_errors: u8,
-    has_overflow: bool,
-    additional: u8,
+    pub num_errors: u8,
+    pub has_overflow: bool,
+    pub additional: u8,
+}
+
+impl RDDIDResult {
+    // Decode the dummy byte plus three ID bytes returned by RDDID
+    pub(crate) fn from_words(buffer: &[u8; 4]) -> Self {
+        RDDIDResult {
+            lcd_manufacturer_id: buffer[1],
+            lcd_driver_version: buffer[2],
+            lcd_driver_id: buffer[3],
+        }
+    }
+
+    /// The three ID bytes, as RDDID returns them after its dummy byte.
+    pub fn to_bytes(&self) -> [u8; 3] {
+        [self.lcd_manufacturer_id, self.lcd_driver_version, self.lcd_driver_id]
+    }
+}
+
+impl RNEDSIResult {
+    // Decode the dummy byte plus error count byte returned by RNEDSI
+    pub(crate) fn from_words(ret_words: &[u8; 2]) -> Self {
+        RNEDSIResult {
+            num_errors: ret_words[1] & 0x7f,
+            has_overflow: ( ret_words[1] & 0x80 ) > 0,
+            additional: 0,
+        }
+    }
+
+    /// The error count byte, as RNEDSI returns it after its dummy byte.
+    pub fn to_bytes(&self) -> [u8; 1] {
+        [(self.num_errors & 0x7f) | if self.has_overflow { 0x80 } else { 0 }]
+    }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum BoosterVoltageStatus {
     OFF,
     ON,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum AddressOrder {
     INCREMENT,
     DECREMENT,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum RowColumnExchange {
     NORMAL,
     EXCHANGE,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum RgbOrder {
     RGB,
     BGR,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum PixelFormat {
     Undefined,
     Bit16,
@@ -103,24 +152,61 @@ pub enum PixelFormat {
     Bit24,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum OnOff {
     OFF,
     ON,
 }
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum InOut {
     IN,
     OUT,
 }
 
-#[derive(Debug)]
+/// Sleep, display, partial/normal and idle state as last commanded by the
+/// driver.
+///
+/// A freshly reset panel is in sleep-in with the display off, in normal mode
+/// with idle mode off.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PowerState {
+    pub sleep: InOut,
+    pub display: OnOff,
+    pub mode: DisplayMode,
+    pub idle: OnOff,
+}
+
+impl PowerState {
+    pub(crate) const RESET: PowerState = PowerState {
+        sleep: InOut::IN,
+        display: OnOff::OFF,
+        mode: DisplayMode::NORMAL,
+        idle: OnOff::OFF,
+    };
+
+    // The state a panel reports through RDDPM
+    pub(crate) fn from_rddpm(pm: &RDDPMResult) -> Self {
+        PowerState {
+            sleep: pm.slpout,
+            display: pm.dison,
+            mode: pm.noron,
+            idle: pm.idmon,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum DisplayMode {
     PARTIAL,
     NORMAL,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum GammaCurveSelect {
     Undefined,
     GC0,
@@ -129,219 +215,651 @@ pub enum GammaCurveSelect {
     GC3,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum TearingEffect {
     MODE1,
     MODE2,
 }
 
-#[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct RDDSTResult {
-    bston: BoosterVoltageStatus, // Booster voltage status
-    my: AddressOrder,            // Row address order
-    mx: AddressOrder,            // Column address order
-    mv: RowColumnExchange,       // Row/column exchange
-    ml: AddressOrder,            // Scan address order
-    rgb: RgbOrder,               // RGB order
-    ifpf: PixelFormat,           // Interface color pixel format definition
-    idmon: OnOff,                // Idle mode on/off
-    ptlon: OnOff,                // Partial mode on/off
-    slpout: InOut,               // Sleep in/out
-    noron: DisplayMode,          // Display normal mode on/off,
-    st: OnOff,                   // Vertical scrolling status,
-    invon: OnOff,                // Inversion status
-    dison: OnOff,                // Display on/off
-    teon: OnOff,                 // Tearing effect line on/off
-    gcsel: GammaCurveSelect,     // Gamma curve selection
-    tem: TearingEffect,          // Tearing effect line mode
+    pub bston: BoosterVoltageStatus,  // Booster voltage status
+    pub my: AddressOrder,             // Row address order
+    pub mx: AddressOrder,             // Column address order
+    pub mv: RowColumnExchange,        // Row/column exchange
+    pub ml: AddressOrder,             // Scan address order
+    pub rgb: RgbOrder,                // RGB order
+    pub ifpf: PixelFormat,            // Interface color pixel format definition
+    pub idmon: OnOff,                 // Idle mode on/off
+    pub ptlon: OnOff,                 // Partial mode on/off
+    pub slpout: InOut,                // Sleep in/out
+    pub noron: DisplayMode,           // Display normal mode on/off,
+    pub st: OnOff,                    // Vertical scrolling status,
+    pub invon: OnOff,                 // Inversion status
+    pub dison: OnOff,                 // Display on/off
+    pub teon: OnOff,                  // Tearing effect line on/off
+    pub gcsel: GammaCurveSelect,      // Gamma curve selection
+    pub tem: TearingEffect,           // Tearing effect line mode
+}
+
+impl RDDSTResult {
+    // Decode the dummy byte plus four status bytes returned by RDDST
+    pub(crate) fn from_words(ret_words: &[u8; 5]) -> Self {
+        RDDSTResult {
+            bston: match ret_words[1] & 0x80 {
+                0 => BoosterVoltageStatus::OFF,
+                _ => BoosterVoltageStatus::ON,
+            },
+            my: match ret_words[1] & 0x40 {
+                0 => AddressOrder::INCREMENT,
+                _ => AddressOrder::DECREMENT,
+            },
+            mx: match ret_words[1] & 0x20 {
+                0 => AddressOrder::INCREMENT,
+                _ => AddressOrder::DECREMENT,
+            },
+            mv: match ret_words[1] & 0x10 {
+                0 => RowColumnExchange::NORMAL,
+                _ => RowColumnExchange::EXCHANGE,
+            },
+            ml: match ret_words[1] & 0x08 {
+                0 => AddressOrder::INCREMENT,
+                _ => AddressOrder::DECREMENT,
+            },
+            rgb: match ret_words[1] & 0x04 {
+                0 => RgbOrder::RGB,
+                _ => RgbOrder::BGR,
+            },
+            ifpf: match ret_words[2] & 0x70 {
+                0x50 => PixelFormat::Bit16,
+                0x60 => PixelFormat::Bit18,
+                0x70 => PixelFormat::Bit24,
+                _ => PixelFormat::Undefined,
+            },
+            idmon: match ret_words[2] & 0x08 {
+                0 => OnOff::OFF,
+                _ => OnOff::ON,
+            },
+            ptlon: match ret_words[2] & 0x04 {
+                0 => OnOff::OFF,
+                _ => OnOff::ON,
+            },
+            slpout: match ret_words[2] & 0x02 {
+                0 => InOut::IN,
+                _ => InOut::OUT,
+            },
+            noron: match ret_words[2] & 0x01 {
+                0 => DisplayMode::PARTIAL,
+                _ => DisplayMode::NORMAL,
+            },
+            st: match ret_words[3] & 0x80 {
+                0 => OnOff::OFF,
+                _ => OnOff::ON,
+            },
+            invon: match ret_words[3] & 0x20 {
+                0 => OnOff::OFF,
+                _ => OnOff::ON,
+            },
+            dison: match ret_words[3] & 0x04 {
+                0 => OnOff::OFF,
+                _ => OnOff::ON,
+            },
+            teon: match ret_words[3] & 0x02 {
+                0 => OnOff::OFF,
+                _ => OnOff::ON,
+            },
+            gcsel: match ret_words[3] & 0x01 {
+                0 => match ret_words[4] & 0xc0 {
+                    0x0 => GammaCurveSelect::GC0,
+                    0x40 => GammaCurveSelect::GC1,
+                    0x80 => GammaCurveSelect::GC2,
+                    0xc0 => GammaCurveSelect::GC3,
+                    _ => GammaCurveSelect::Undefined,
+                },
+                _ => GammaCurveSelect::Undefined,
+            },
+            tem: match ret_words[4] & 0x20 {
+                0 => TearingEffect::MODE1,
+                _ => TearingEffect::MODE2,
+            }
+        }
+    }
+
+    /// The four status bytes, as RDDST returns them after its dummy byte.
+    pub fn to_bytes(&self) -> [u8; 4] {
+        let b1 = bit(self.bston == BoosterVoltageStatus::ON, 0x80)
+            | bit(self.my == AddressOrder::DECREMENT, 0x40)
+            | bit(self.mx == AddressOrder::DECREMENT, 0x20)
+            | bit(self.mv == RowColumnExchange::EXCHANGE, 0x10)
+            | bit(self.ml == AddressOrder::DECREMENT, 0x08)
+            | bit(self.rgb == RgbOrder::BGR, 0x04);
+        let b2 = match self.ifpf {
+                PixelFormat::Bit16 => 0x50,
+                PixelFormat::Bit18 => 0x60,
+                PixelFormat::Bit24 => 0x70,
+                PixelFormat::Undefined => 0x00,
+            }
+            | bit(self.idmon == OnOff::ON, 0x08)
+            | bit(self.ptlon == OnOff::ON, 0x04)
+            | bit(self.slpout == InOut::OUT, 0x02)
+            | bit(self.noron == DisplayMode::NORMAL, 0x01);
+        let b3 = bit(self.st == OnOff::ON, 0x80)
+            | bit(self.invon == OnOff::ON, 0x20)
+            | bit(self.dison == OnOff::ON, 0x04)
+            | bit(self.teon == OnOff::ON, 0x02)
+            | bit(self.gcsel == GammaCurveSelect::Undefined, 0x01);
+        let b4 = match self.gcsel {
+                GammaCurveSelect::GC1 => 0x40,
+                GammaCurveSelect::GC2 => 0x80,
+                GammaCurveSelect::GC3 => 0xc0,
+                _ => 0x00,
+            }
+            | bit(self.tem == TearingEffect::MODE2, 0x20);
+        [b1, b2, b3, b4]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RDDPMResult {
+    pub bston: BoosterVoltageStatus,  // Booster voltage status
+    pub idmon: OnOff,                 // Idle mode on/off
+    pub ptlon: OnOff,                 // Partial mode on/off
+    pub slpout: InOut,                // Sleep in/out
+    pub noron: DisplayMode,           // Display normal mode on/off
+    pub dison: OnOff,                 // Display on/off
+}
+
+impl RDDPMResult {
+    // Decode the dummy byte plus power mode byte returned by RDDPM
+    pub(crate) fn from_words(ret_words: &[u8; 2]) -> Self {
+        RDDPMResult {
+            bston: match ret_words[1] & 0x80 {
+                0 => BoosterVoltageStatus::OFF,
+                _ => BoosterVoltageStatus::ON,
+            },
+            idmon: match ret_words[1] & 0x40 {
+                0 => OnOff::OFF,
+                _ => OnOff::ON,
+            },
+            ptlon: match ret_words[1] & 0x20 {
+                0 => OnOff::OFF,
+                _ => OnOff::ON,
+            },
+            slpout: match ret_words[1] & 0x10 {
+                0 => InOut::IN,
+                _ => InOut::OUT,
+            },
+            noron: match ret_words[1] & 0x08 {
+                0 => DisplayMode::PARTIAL,
+                _ => DisplayMode::NORMAL,
+            },
+            dison: match ret_words[1] & 0x04 {
+                0 => OnOff::OFF,
+                _ => OnOff::ON,
+            },
+        }
+    }
+
+    /// The power mode byte, as RDDPM returns it after its dummy byte.
+    pub fn to_bytes(&self) -> [u8; 1] {
+        [bit(self.bston == BoosterVoltageStatus::ON, 0x80)
+            | bit(self.idmon == OnOff::ON, 0x40)
+            | bit(self.ptlon == OnOff::ON, 0x20)
+            | bit(self.slpout == InOut::OUT, 0x10)
+            | bit(self.noron == DisplayMode::NORMAL, 0x08)
+            | bit(self.dison == OnOff::ON, 0x04)]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RDDMADCTLResult {
+    pub my: AddressOrder,             // Row address order
+    pub mx: AddressOrder,             // Column address order
+    pub mv: RowColumnExchange,        // Row/column exchange
+    pub ml: AddressOrder,             // Vertical refresh order
+    pub rgb: RgbOrder,                // RGB order
+    pub mh: AddressOrder,             // Horizontal refresh order
+}
+
+impl RDDMADCTLResult {
+    // Decode the dummy byte plus MADCTL byte returned by RDDMADCTL
+    pub(crate) fn from_words(ret_words: &[u8; 2]) -> Self {
+        RDDMADCTLResult {
+            my: match ret_words[1] & 0x80 {
+                0 => AddressOrder::INCREMENT,
+                _ => AddressOrder::DECREMENT,
+            },
+            mx: match ret_words[1] & 0x40 {
+                0 => AddressOrder::INCREMENT,
+                _ => AddressOrder::DECREMENT,
+            },
+            mv: match ret_words[1] & 0x20 {
+                0 => RowColumnExchange::NORMAL,
+                _ => RowColumnExchange::EXCHANGE,
+            },
+            ml: match ret_words[1] & 0x10 {
+                0 => AddressOrder::INCREMENT,
+                _ => AddressOrder::DECREMENT,
+            },
+            rgb: match ret_words[1] & 0x08 {
+                0 => RgbOrder::RGB,
+                _ => RgbOrder::BGR,
+            },
+            mh: match ret_words[1] & 0x04 {
+                0 => AddressOrder::INCREMENT,
+                _ => AddressOrder::DECREMENT,
+            },
+        }
+    }
+
+    /// The MADCTL byte, as RDDMADCTL returns it after its dummy byte.
+    pub fn to_bytes(&self) -> [u8; 1] {
+        [bit(self.my == AddressOrder::DECREMENT, 0x80)
+            | bit(self.mx == AddressOrder::DECREMENT, 0x40)
+            | bit(self.mv == RowColumnExchange::EXCHANGE, 0x20)
+            | bit(self.ml == AddressOrder::DECREMENT, 0x10)
+            | bit(self.rgb == RgbOrder::BGR, 0x08)
+            | bit(self.mh == AddressOrder::DECREMENT, 0x04)]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RDDPIXFMTResult {
+    pub rgb_format: PixelFormat,      // RGB interface color format
+    pub control_format: PixelFormat,  // Control interface color format
+}
+
+impl RDDPIXFMTResult {
+    // Decode the dummy byte plus COLMOD byte returned by RDDPIXFMT
+    pub(crate) fn from_words(ret_words: &[u8; 2]) -> Self {
+        RDDPIXFMTResult {
+            rgb_format: pixel_format_from_bits((ret_words[1] >> 4) & 0x07),
+            control_format: pixel_format_from_bits(ret_words[1] & 0x07),
+        }
+    }
+
+    /// The COLMOD byte, as RDDPIXFMT returns it after its dummy byte.
+    pub fn to_bytes(&self) -> [u8; 1] {
+        [(pixel_format_to_bits(self.rgb_format) << 4) | pixel_format_to_bits(self.control_format)]
+    }
+}
+
+// Decode a 3-bit COLMOD field
+fn pixel_format_from_bits(bits: u8) -> PixelFormat {
+    match bits {
+        0x5 => PixelFormat::Bit16,
+        0x6 => PixelFormat::Bit18,
+        0x7 => PixelFormat::Bit24,
+        _ => PixelFormat::Undefined,
+    }
+}
+
+// Encode a 3-bit COLMOD field
+fn pixel_format_to_bits(format: PixelFormat) -> u8 {
+    match format {
+        PixelFormat::Bit16 => 0x5,
+        PixelFormat::Bit18 => 0x6,
+        PixelFormat::Bit24 => 0x7,
+        PixelFormat::Undefined => 0x0,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RDDIMResult {
+    pub vsson: OnOff,                 // Vertical scrolling on/off
+    pub invon: OnOff,                 // Inversion on/off
+    pub gcsel: GammaCurveSelect,      // Gamma curve selection
+}
+
+impl RDDIMResult {
+    // Decode the dummy byte plus image mode byte returned by RDDIM
+    pub(crate) fn from_words(ret_words: &[u8; 2]) -> Self {
+        RDDIMResult {
+            vsson: match ret_words[1] & 0x80 {
+                0 => OnOff::OFF,
+                _ => OnOff::ON,
+            },
+            invon: match ret_words[1] & 0x20 {
+                0 => OnOff::OFF,
+                _ => OnOff::ON,
+            },
+            gcsel: match ret_words[1] & 0x07 {
+                0 => GammaCurveSelect::GC0,
+                1 => GammaCurveSelect::GC1,
+                2 => GammaCurveSelect::GC2,
+                3 => GammaCurveSelect::GC3,
+                _ => GammaCurveSelect::Undefined,
+            },
+        }
+    }
+
+    /// The image mode byte, as RDDIM returns it after its dummy byte.
+    pub fn to_bytes(&self) -> [u8; 1] {
+        let gc = match self.gcsel {
+            GammaCurveSelect::GC0 => 0,
+            GammaCurveSelect::GC1 => 1,
+            GammaCurveSelect::GC2 => 2,
+            GammaCurveSelect::GC3 => 3,
+            GammaCurveSelect::Undefined => 4,
+        };
+        [bit(self.vsson == OnOff::ON, 0x80) | bit(self.invon == OnOff::ON, 0x20) | gc]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RDDSMResult {
+    pub teon: OnOff,                  // Tearing effect line on/off
+    pub tem: TearingEffect,           // Tearing effect line mode
+    pub hs: OnOff,                    // Horizontal sync (RGB interface)
+    pub vs: OnOff,                    // Vertical sync (RGB interface)
+    pub pixel_clk: OnOff,             // Pixel clock (RGB interface)
+    pub data_en: OnOff,               // Data enable (RGB interface)
+    pub error_dsi: bool,              // Error on DSI
+}
+
+impl RDDSMResult {
+    // Decode the dummy byte plus signal mode byte returned by RDDSM
+    pub(crate) fn from_words(ret_words: &[u8; 2]) -> Self {
+        RDDSMResult {
+            teon: match ret_words[1] & 0x80 {
+                0 => OnOff::OFF,
+                _ => OnOff::ON,
+            },
+            tem: match ret_words[1] & 0x40 {
+                0 => TearingEffect::MODE1,
+                _ => TearingEffect::MODE2,
+            },
+            hs: match ret_words[1] & 0x20 {
+                0 => OnOff::OFF,
+                _ => OnOff::ON,
+            },
+            vs: match ret_words[1] & 0x10 {
+                0 => OnOff::OFF,
+                _ => OnOff::ON,
+            },
+            pixel_clk: match ret_words[1] & 0x08 {
+                0 => OnOff::OFF,
+                _ => OnOff::ON,
+            },
+            data_en: match ret_words[1] & 0x04 {
+                0 => OnOff::OFF,
+                _ => OnOff::ON,
+            },
+            error_dsi: ( ret_words[1] & 0x01 ) > 0,
+        }
+    }
+
+    /// The signal mode byte, as RDDSM returns it after its dummy byte.
+    pub fn to_bytes(&self) -> [u8; 1] {
+        [bit(self.teon == OnOff::ON, 0x80)
+            | bit(self.tem == TearingEffect::MODE2, 0x40)
+            | bit(self.hs == OnOff::ON, 0x20)
+            | bit(self.vs == OnOff::ON, 0x10)
+            | bit(self.pixel_clk == OnOff::ON, 0x08)
+            | bit(self.data_en == OnOff::ON, 0x04)
+            | bit(self.error_dsi, 0x01)]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RDDSDRResult {
+    pub register_loading: bool,       // Register loading detection
+    pub functionality: bool,          // Functionality detection
+    pub checksum_mismatch: bool,      // First and continue checksums differ
+}
+
+impl RDDSDRResult {
+    // Decode the dummy byte plus self-diagnostic byte returned by RDDSDR
+    pub(crate) fn from_words(ret_words: &[u8; 2]) -> Self {
+        RDDSDRResult {
+            register_loading: ( ret_words[1] & 0x80 ) > 0,
+            functionality: ( ret_words[1] & 0x40 ) > 0,
+            checksum_mismatch: ( ret_words[1] & 0x01 ) > 0,
+        }
+    }
+
+    /// The self-diagnostic byte, as RDDSDR returns it after its dummy byte.
+    pub fn to_bytes(&self) -> [u8; 1] {
+        [bit(self.register_loading, 0x80)
+            | bit(self.functionality, 0x40)
+            | bit(self.checksum_mismatch, 0x01)]
+    }
+}
+
+// Set `mask` if `on`, for re-encoding status bytes
+fn bit(on: bool, mask: u8) -> u8 {
+    if on { mask } else { 0 }
 }
 
 pub enum InstructionInput {
     NoInput,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum InstructionResult {
     NoReturn,
     RDDIDReturn(RDDIDResult),
     RNEDSIReturn(RNEDSIResult),
     RDDSTReturn(RDDSTResult),
+    RDDPMReturn(RDDPMResult),
+    RDDMADCTLReturn(RDDMADCTLResult),
+    RDDPIXFMTReturn(RDDPIXFMTResult),
+    RDDIMReturn(RDDIMResult),
+    RDDSMReturn(RDDSMResult),
+    RDDSDRReturn(RDDSDRResult),
+    RDTESCANReturn(u16),
+    RDDISBVReturn(u8),
+    RDCTRLDReturn(DisplayControl),
+    RDCABCReturn(CabcMode),
+    RDCABCMBReturn(u8),
 }
 
-// Callback for printing debug info
-pub fn dummy_debug_info(_s: &str) {
-    // Do nothing.
-}
-
-impl<P, CS, DC, RST, T> ST7796S<P, CS, DC, RST, T> 
+impl<DI, RST, T> ST7796S<DI, RST, T>
 where 
-    P: ValidSpiPinout<pac::SPI0>,
-    CS: OutputPin,
-    DC: OutputPin,
-    RST: OutputPin,
-    T: embedded_hal::delay::DelayNs,
+    DI: DisplayInterface,
+    RST: OutputPin<Error = DI::PinError>,
 {
-    pub fn new<F: FnMut(&str)>(
-            pins: P,
-            clocks: rp235x_hal::clocks::ClocksManager,
-            spi0: pac::SPI0,
-            resets: &mut pac::RESETS,
-            cs: CS,
-            dc: DC,
+    /// Create a driver from a display interface and the reset pin.
+    ///
+    /// The driver assumes a freshly reset panel until `init` runs; see
+    /// `sync_power_state` for taking over one that is already running.
+    pub fn new(
+            interface: DI,
             rst: RST,
-            timer: T,
-            mut debug_cb: F) -> Self {
-
-        debug_cb("Initializing SPI device.\r\n");
-        // These are implicitly used by the spi driver if they are in the correct mode
-        let s = hal::spi::Spi::new(spi0, pins).init(
-            resets,
-            clocks.peripheral_clock.freq(),
-            62500.kHz(),
-            // 16.MHz(),
-            // 100_000.kHz(),
-            embedded_hal::spi::MODE_0,
-        );
+            timer: T) -> Self {
+        ST7796S {
+            interface,
+            rst: Some(rst),
+            timer,
+            te: NoPin::new(),
+            shadow: Shadow::RESET,
+        }
+    }
+
+    /// Tear the driver down, returning the display interface, the reset pin
+    /// (if there is one) and the delay provider. Detach a TE pin with
+    /// `without_te_pin` first.
+    pub fn release(self) -> (DI, Option<RST>, T) {
+        (self.interface, self.rst, self.timer)
+    }
 
+    /// Attach the panel's TE output, for `wait_for_te`.
+    pub fn with_te_pin<TE>(self, te: TE) -> ST7796S<DI, RST, T, TE> {
         ST7796S {
-            interface: s,
-            cs: cs,
-            dc: dc,
-            rst: rst,
-            timer: timer,
+            interface: self.interface,
+            rst: self.rst,
+            timer: self.timer,
+            te,
+            shadow: self.shadow,
         }
     }
+}
 
-    pub fn init(&mut self) {
+impl<DI, RST, T, TE> ST7796S<DI, RST, T, TE>
+where 
+    DI: DisplayInterface,
+    RST: OutputPin<Error = DI::PinError>,
+{
+    /// Detach the TE pin, handing it back along with the driver.
+    pub fn without_te_pin(self) -> (ST7796S<DI, RST, T>, TE) {
+        let driver = ST7796S {
+            interface: self.interface,
+            rst: self.rst,
+            timer: self.timer,
+            te: NoPin::new(),
+            shadow: self.shadow,
+        };
+        (driver, self.te)
+    }
 
-        self.reset_pin();
+    // Put `tx` on the bus and adopt the state it leads to, leaving any
+    // datasheet wait to the caller
+    pub(crate) fn send(&mut self, tx: Transaction) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        for reg in tx.writes() {
+            self.write_command(reg.cmd)?;
+            if !reg.data().is_empty() {
+                self.write_data(reg.data())?;
+            }
+        }
+        let mut words = [0u8; REPLY_MAX];
+        if let Some((cmd, len)) = tx.reply() {
+            self.read(cmd, &mut words[..len])?;
+        }
+        let (result, shadow) = tx.finish(&words);
+        self.shadow = shadow;
+        Ok(result)
+    }
 
-        // 2. Software Reset
-        self.swreset().unwrap();
-        self.timer.delay_ms(10);
+    // Stream a flood after a RAMWR, straight to the interface so every chunk
+    // isn't traced on its own
+    fn flood(&mut self, mut flood: Flood) -> Result<(), Error<DI::BusError, DI::PinError>> {
+        while let Some(chunk) = flood.next_chunk() {
+            self.interface.write_data(chunk)?;
+        }
+        Ok(())
+    }
 
-        // 3. Sleep Out
-        self.write_command(0x11); // SLPOUT
-        self.timer.delay_ms(120);
+    // Drive the reset pin, if there is one. Returns whether there was.
+    fn reset_pin(&mut self, high: bool) -> Result<bool, Error<DI::BusError, DI::PinError>> {
+        let rst = match self.rst.as_mut() {
+            Some(rst) => rst,
+            None => return Ok(false),
+        };
+        if high {
+            rst.set_high().map_err(Error::Pin)?;
+        } else {
+            rst.set_low().map_err(Error::Pin)?;
+            self.shadow = Shadow::RESET;
+        }
+        Ok(true)
+    }
 
-        // 4. Display ON
-        self.write_command(0x29); // DISPON
-        self.timer.delay_ms(20);
+    // Helper function to write a command to the display interface
+    fn write_command(&mut self, cmd: u8) -> Result<(), Error<DI::BusError, DI::PinError>> {
+        trace!("cmd {:#x}", cmd);
+        self.interface.write_command(cmd)
+    }
 
-        // 5. Set pixel format
-        self.write_command(0x3A); 
-        self.write_data(&[0x55]); // 16-bit color (RGB565)
+    // Helper function to write data to the display interface
+    fn write_data(&mut self, data: &[u8]) -> Result<(), Error<DI::BusError, DI::PinError>> {
+        trace!("data {:?}", data);
+        self.interface.write_data(data)
+    }
 
-        // 6. Set memory access control (optional, orientation)
-        self.write_command(0x36); 
-        self.write_data(&[0x00]); // Row/column order (adjust as needed)
+    // Helper function to send a read command and read back its reply
+    fn read(&mut self, cmd: u8, data: &mut [u8]) -> Result<(), Error<DI::BusError, DI::PinError>> {
+        self.interface.read(cmd, data)?;
+        trace!("read {:#x} -> {:?}", cmd, data);
+        Ok(())
+    }
 
-        self.write_command(0x2A);
-        self.write_data(&[0x00, 0x00, 0x00, 0xEF]);         // CASET: column address set
-        self.write_command(0x2B);
-        self.write_data(&[0x00, 0x00, 0x01, 0xDF]);         // RASET: row address set
-        self.write_command(0x2C);
-        for _ in 0..(479*319) {
-            // self.write_data(&[0xff, 0xff])
-            self.write_data(&[0x00, 0x00])
-        }
+    /// Set the CASET/RASET window that following pixel writes land in.
+    ///
+    /// Coordinates are inclusive and logical, i.e. relative to the current
+    /// orientation; the panel maps them through MADCTL. The window must lie
+    /// within `size()`.
+    pub fn set_window(&mut self, x0: u16, y0: u16, x1: u16, y1: u16) -> Result<(), Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.set_window(x0, y0, x1, y1)?)?;
+        Ok(())
+    }
 
-        // self.write_command(0x29);          // Display on
-        // self.timer.delay_ms(10);
-        // self.timer.delay_ms(120);
-    }
-
-    // Helper function to write a command to the SPI interface
-    fn write_command(&mut self, cmd: u8) {
-        self.cs.set_low().unwrap();
-        self.dc.set_low().unwrap();
-        self.interface.write(&[cmd]).unwrap();
-        self.cs.set_high().unwrap();
-    }
-
-    // Helper function to write data to the SPI interface
-    fn write_data(&mut self, data: &[u8]) {
-        self.cs.set_low().unwrap();
-        self.dc.set_high().unwrap();
-        self.interface.write(data).unwrap();
-        self.cs.set_high().unwrap();
-    }
-
-    #[allow(dead_code)]
-    // Helper function to read data from the SPI interface
-    fn read_data(&mut self, data: &mut [u8]) {
-        self.cs.set_low().unwrap();
-        self.dc.set_high().unwrap();
-        self.interface.read(data).unwrap();
-        self.cs.set_high().unwrap();
-    }
-
-    // Toggle the reset pin
-    fn reset_pin(&mut self) {
-        self.rst.set_high().unwrap();
-        self.timer.delay_ms(50);
-        self.rst.set_low().unwrap();
-        self.timer.delay_ms(50);
-        self.rst.set_high().unwrap();
-        self.timer.delay_ms(120);
-    }
-    
-    pub fn exec(&mut self, command: Command, _inp: InstructionInput) -> Result<InstructionResult, u8> {
-        match command {
-            Command::NOP => self.nop(),
-            Command::SWRESET => self.swreset(),
-            Command::RDDID => {
-                self.rddid()
-            },
-            _ => Ok(InstructionResult::NoReturn)
-        }
+    /// Set rotation, horizontal mirroring and colour order (MADCTL).
+    ///
+    /// Switches the logical size between 320x480 and 480x320, which
+    /// `set_window` then checks against. GRAM contents are not redrawn.
+    pub fn set_orientation(&mut self, rotation: Rotation, mirror: bool, rgb_order: RgbOrder) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.set_orientation(rotation, mirror, rgb_order))
     }
 
+    /// MADCTL settings the driver last wrote.
+    pub fn orientation(&self) -> Orientation {
+        self.shadow.orientation
+    }
+
+    /// Logical (width, height) of the screen in the current orientation.
+    pub fn size(&self) -> (u16, u16) {
+        self.shadow.orientation.size()
+    }
+
+    /// Sleep and display state the driver last put the panel in.
+    pub fn power_state(&self) -> PowerState {
+        self.shadow.power
+    }
+
+    /// Read the panel's power mode (RDDPM) and adopt it as the tracked state.
+    ///
+    /// For taking over a panel that is already running without calling
+    /// `init`; otherwise the driver assumes sleep-in and rejects `dispon`.
     #[cold]
-    // Loop back test. Kind of doesn't work.
-    pub fn loopback_test(&mut self) -> Result<InstructionResult, u8> {
-        let tx_buffer: [u8; 4]  = [0x00, 0xAA, 0xFF, 0x55];
-        let mut rx_buffer: [u8; 4] = [0u8; 4];
-        match self.interface.transfer(&mut rx_buffer, &tx_buffer) {
-            Ok(_x) => {
-                if tx_buffer == rx_buffer {
-                    Ok(InstructionResult::NoReturn)
-                } else {
-                    Err(1)
-                }
-            },
-            _ => Err(0),
-        }
+    pub fn sync_power_state(&mut self) -> Result<PowerState, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.sync_power_state())?;
+        Ok(self.shadow.power)
     }
 
-    // No operation
-    pub fn nop(&mut self) -> Result<InstructionResult, u8> {
-        self.write_command(Command::NOP.into());
-        Ok(InstructionResult::NoReturn)
+    /// Start a RAMWR and stream RGB565 pixels into the current window.
+    pub fn write_pixels<I>(&mut self, pixels: I) -> Result<(), Error<DI::BusError, DI::PinError>>
+    where
+        I: IntoIterator<Item = u16>,
+    {
+        self.write_command(Command::RAMWR.into())?;
+        debug!("streaming pixels");
+        self.interface.write_pixels(pixels)
     }
 
-    // Software reset
-    pub fn swreset(&mut self) -> Result<InstructionResult, u8> {
-        self.write_command(Command::SWRESET.into());
-        Ok(InstructionResult::NoReturn)
+    /// Start a RAMWR and write `count` RGB565 pixels of a single colour.
+    pub fn fill(&mut self, color: u16, count: u32) -> Result<(), Error<DI::BusError, DI::PinError>> {
+        self.write_command(Command::RAMWR.into())?;
+        self.flood(Flood::new(&color.to_be_bytes(), count))
+    }
+
+    /// Send RAMRD and read raw frame memory from the current window.
+    ///
+    /// `data[0]` receives the dummy byte the panel clocks out first; pixel data
+    /// follows in the read-back format of the current COLMOD setting. On an
+    /// RP2350 `ClockedSpi` the transfer runs at the configured read clock.
+    #[cold]
+    pub fn ramrd(&mut self, data: &mut [u8]) -> Result<(), Error<DI::BusError, DI::PinError>> {
+        self.read(Command::RAMRD.into(), data)
+    }
+
+    // No operation
+    pub fn nop(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.nop())
     }
 
     // Read Display ID
     #[cold]
-    pub fn rddid(&mut self) -> Result<InstructionResult, u8> {
-        self.write_command(Command::RDDID.into());
-        let mut buffer: [u8; 4] = [0, 0, 0, 0];
-        self.read_data(&mut buffer);
-        Ok(
-            InstructionResult::RDDIDReturn(
-                RDDIDResult {
-                    lcd_manufacturer_id: buffer[1],
-                    lcd_driver_version: buffer[2],
-                    lcd_driver_id: buffer[3],
-                }
-            )
-        )
+    pub fn rddid(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.rddid())
     }
 
     // // Alternative implementation of rddid
@@ -400,137 +918,347 @@ where
 
     // Read Number of the Errors on DSI
     #[cold]
-    pub fn rnedsi(&mut self) -> Result<InstructionResult, u8> {
-        self.write_command(Command::RNEDSI.into());
-        let mut ret_words: [u8; 2] = [0, 0];
-        self.read_data(&mut ret_words);
-        Ok(
-            InstructionResult::RNEDSIReturn(
-                RNEDSIResult {
-                    num_errors: ret_words[1] & 0x7f,
-                    has_overflow: ( ret_words[1] & 0x80 ) > 0,
-                    additional: 0,
-                }
-            )
-        )
+    pub fn rnedsi(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.rnedsi())
     }
 
     // Read Display Status
     #[cold]
-    pub fn rddst(&mut self) -> Result<InstructionResult, u8> {
-        self.write_command(Command::RDDST.into());
-        let mut ret_words: [u8; 5] = [0, 0, 0, 0, 0];
-        self.read_data(&mut ret_words);
-        Ok(
-            InstructionResult::RDDSTReturn(
-                RDDSTResult {
-                    bston: match ret_words[1] & 0x80 {
-                        0 => BoosterVoltageStatus::OFF,
-                        _ => BoosterVoltageStatus::ON,
-                    },
-                    my: match ret_words[1] & 0x40 {
-                        0 => AddressOrder::INCREMENT,
-                        _ => AddressOrder::DECREMENT,
-                    },           
-                    mx: match ret_words[1] & 0x20 {
-                        0 => AddressOrder::INCREMENT,
-                        _ => AddressOrder::DECREMENT,
-                    },           
-                    mv: match ret_words[1] & 0x10 {
-                        0 => RowColumnExchange::NORMAL,
-                        _ => RowColumnExchange::EXCHANGE,
-                    },      
-                    ml: match ret_words[1] & 0x08 {
-                        0 => AddressOrder::INCREMENT,
-                        _ => AddressOrder::DECREMENT,
-                    },           
-                    rgb: match ret_words[1] & 0x04 {
-                        0 => RgbOrder::RGB,
-                        _ => RgbOrder::BGR,
-                    },              
-                    ifpf: match ret_words[2] & 0x70 {
-                        0x50 => PixelFormat::Bit16,
-                        0x60 => PixelFormat::Bit18,
-                        0x70 => PixelFormat::Bit24,
-                        _ => PixelFormat::Undefined,
-                    },          
-                    idmon: match ret_words[2] & 0x08 {
-                        0 => OnOff::OFF,
-                        _ => OnOff::ON,
-                    },               
-                    ptlon: match ret_words[2] & 0x04 {
-                        0 => OnOff::OFF,
-                        _ => OnOff::ON,
-                    },               
-                    slpout: match ret_words[2] & 0x02 {
-                        0 => InOut::IN,
-                        _ => InOut::OUT,
-                    },              
-                    noron: match ret_words[2] & 0x01 {
-                        0 => DisplayMode::PARTIAL,
-                        _ => DisplayMode::NORMAL,
-                    },         
-                    st: match ret_words[3] & 0x80 {
-                        0 => OnOff::OFF,
-                        _ => OnOff::ON,
-                    },                  
-                    invon: match ret_words[3] & 0x20 {
-                        0 => OnOff::OFF,
-                        _ => OnOff::ON,
-                    },               
-                    dison: match ret_words[3] & 0x04 {
-                        0 => OnOff::OFF,
-                        _ => OnOff::ON,
-                    },               
-                    teon: match ret_words[3] & 0x02 {
-                        0 => OnOff::OFF,
-                        _ => OnOff::ON,
-                    },                
-                    gcsel: match ret_words[3] & 0x01 {
-                        0 => match ret_words[4] & 0xc0 {
-                            0x0 => GammaCurveSelect::GC0,
-                            0x40 => GammaCurveSelect::GC1,
-                            0x80 => GammaCurveSelect::GC2,
-                            0xc0 => GammaCurveSelect::GC3,
-                            _ => GammaCurveSelect::Undefined,
-                        },
-                        _ => GammaCurveSelect::Undefined,
-                    },
-                    tem: match ret_words[4] & 0x20 {
-                        0 => TearingEffect::MODE1,
-                        _ => TearingEffect::MODE2,
-                    }
-                }
-            )
-        )
+    pub fn rddst(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.rddst())
+    }
+
+    // Read Display Power Mode
+    #[cold]
+    pub fn rddpm(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.rddpm())
+    }
+
+    // Read Display MADCTL
+    #[cold]
+    pub fn rddmadctl(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.rddmadctl())
+    }
+
+    // Read Display Pixel Format
+    #[cold]
+    pub fn rddpixfmt(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.rddpixfmt())
+    }
+
+    // Read Display Image Mode
+    #[cold]
+    pub fn rddim(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.rddim())
+    }
+
+    // Read Display Signal Mode
+    #[cold]
+    pub fn rddsm(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.rddsm())
+    }
+
+    // Read Display Self-Diagnostic Result
+    #[cold]
+    pub fn rddsdr(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.rddsdr())
     }
 
     // Enter inversion off mode
-    pub fn invoff(&mut self) -> Result<InstructionResult, u8> {
-        self.write_command(Command::INVOFF.into());
-        Ok(InstructionResult::NoReturn)
+    pub fn invoff(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.invoff())
     }
 
     // Enter inversion on mode
-    pub fn invon(&mut self) -> Result<InstructionResult, u8> {
-        self.write_command(Command::INVON.into());
-        Ok(InstructionResult::NoReturn)
+    pub fn invon(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.invon())
     }
 
     // Enter display off mode
-    pub fn dispoff(&mut self) -> Result<InstructionResult, u8> {
-        self.write_command(Command::DISPOFF.into());
-        Ok(InstructionResult::NoReturn)
+    pub fn dispoff(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.dispoff())
     }
 
-    // Enter display on mode
-    pub fn dispon(&mut self) -> Result<InstructionResult, u8> {
-        self.write_command(Command::DISPON.into());
-        Ok(InstructionResult::NoReturn)
+    // Enter display on mode. Fails with `Error::InvalidState` while the panel
+    // is asleep.
+    pub fn dispon(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.dispon()?)
+    }
+
+    /// Set the frame memory rows shown in partial mode (PTLAR).
+    ///
+    /// Rows run from 0 to 479. `end_row` may lie above `start_row`, in which
+    /// case the partial area wraps around the bottom of the panel.
+    pub fn ptlar(&mut self, start_row: u16, end_row: u16) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.ptlar(start_row, end_row)?)
+    }
+
+    // Enter partial mode: only the PTLAR rows are driven, the rest show the
+    // non-display colour. Ends vertical scrolling.
+    pub fn ptlon(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.ptlon())
+    }
+
+    // Leave partial mode and drive the whole panel again. Ends vertical
+    // scrolling.
+    pub fn noron(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.noron())
+    }
+
+    // Enter idle mode: only the MSB of each colour component is shown (8
+    // colours) and the FRMCTR2 frame rate applies
+    pub fn idmon(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.idmon())
+    }
+
+    // Leave idle mode and show full colours again
+    pub fn idmoff(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.idmoff())
+    }
+
+    /// Set the idle mode frame rate (FRMCTR2).
+    pub fn frmctr2(&mut self, frame_rate: IdleFrameRate) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.frmctr2(frame_rate)?)
+    }
+}
+
+impl<DI, RST, T, TE> ST7796S<DI, RST, T, TE>
+where
+    DI: DisplayInterface,
+    RST: OutputPin<Error = DI::PinError>,
+    T: DelayNs,
+{
+    /// Bring the panel up with `config`.
+    ///
+    /// Follows the datasheet power-on order: hardware reset (skipped without
+    /// a reset pin, leaving SWRESET to do the job), SWRESET, SLPOUT,
+    /// interface and memory configuration, clearing GRAM and finally DISPON,
+    /// with the required waits in between.
+    pub fn init(&mut self, config: &InitConfig) -> Result<(), Error<DI::BusError, DI::PinError>> {
+        let steps = InitSteps::new(config).ok_or(Error::InvalidParameter)?;
+        for step in steps {
+            match step {
+                InitStep::Reset { high, wait_ms } => {
+                    if self.reset_pin(high)? {
+                        self.delay_ms(wait_ms);
+                    }
+                }
+                InitStep::Send(tx) => {
+                    self.run(tx)?;
+                }
+                InitStep::Fill(area) => self.flood(area.flood(area.height()))?,
+            }
+        }
+        Ok(())
+    }
+
+    // Put `tx` on the bus and wait out its datasheet delay
+    fn run(&mut self, tx: Transaction) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        let wait_ms = tx.wait_ms();
+        let result = self.send(tx)?;
+        if wait_ms > 0 {
+            self.delay_ms(wait_ms);
+        }
+        Ok(result)
+    }
+
+    // Wait out a datasheet timing requirement
+    fn delay_ms(&mut self, ms: u32) {
+        debug!("wait {} ms", ms);
+        self.timer.delay_ms(ms);
+    }
+
+    /// Enter sleep mode (SLPIN).
+    ///
+    /// Turns the display off first, then waits the 120 ms the panel needs
+    /// before it accepts SLPOUT again.
+    pub fn sleep_in(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.sleep_in())
+    }
+
+    /// Leave sleep mode (SLPOUT).
+    ///
+    /// Waits 120 ms for the supplies to settle, which also covers the
+    /// minimum time before SLPIN may be sent again.
+    pub fn sleep_out(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.sleep_out())
+    }
+
+    pub fn exec(&mut self, command: Command, _inp: InstructionInput) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.exec(command)?)
+    }
+
+    // Software reset. Puts the panel back into sleep-in with the display off
+    // and waits the 120 ms required before SLPOUT.
+    pub fn swreset(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.run(self.shadow.swreset())
+    }
+}
+
+impl<DI, T> ST7796S<DI, NoPin<DI::PinError>, T>
+where
+    DI: DisplayInterface,
+    DI::PinError: embedded_hal::digital::Error,
+{
+    /// Create a driver for a module whose RESET isn't connected to a GPIO.
+    ///
+    /// `init` then relies on SWRESET alone to bring the panel to a known state.
+    /// Until then the driver assumes sleep-in with the display off, which
+    /// need not match a panel that was brought up earlier (for example by a
+    /// bootloader); `sync_power_state` reads back the real state.
+    pub fn new_without_reset(
+            interface: DI,
+            timer: T) -> Self {
+        ST7796S {
+            interface,
+            rst: None,
+            timer,
+            te: NoPin::new(),
+            shadow: Shadow::RESET,
+        }
     }
+}
 
-    pub fn wrdisbv(&mut self, _val: u8) -> Result<InstructionResult, u8> {
-        self.write_command(Command::WRDISBV.into());
+impl<SPI, CS, DC, RST, T, TE> ST7796S<SpiInterface<SPI, CS, DC>, RST, T, TE>
+where
+    SPI: embedded_hal::spi::SpiBus,
+    CS: OutputPin,
+    DC: OutputPin<Error = CS::Error>,
+    RST: OutputPin<Error = CS::Error>,
+{
+    #[cold]
+    // Loop back test. Kind of doesn't work.
+    pub fn loopback_test(&mut self) -> Result<InstructionResult, Error<SPI::Error, CS::Error>> {
+        self.interface.loopback_test()?;
         Ok(InstructionResult::NoReturn)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::mock::{self, Op};
+
+    // What `init` sends for `config` on a 320x480 window, up to the GRAM clear
+    fn configure(colmod: u8, madctl: u8, inversion: u8) -> Vec<Op> {
+        vec![
+            Op::Reset(true),
+            Op::DelayMs(50),
+            Op::Reset(false),
+            Op::DelayMs(50),
+            Op::Reset(true),
+            Op::DelayMs(120),
+            Op::Command(0x01),
+            Op::DelayMs(120),
+            Op::Command(0x11),
+            Op::DelayMs(120),
+            Op::Command(0x3A),
+            Op::Data(vec![colmod]),
+            Op::Command(0x36),
+            Op::Data(vec![madctl]),
+            Op::Command(inversion),
+        ]
+    }
+
+    #[test]
+    fn init_sends_power_on_sequence() {
+        let (mut display, log) = mock::display();
+        display.init(&InitConfig::new()).unwrap();
+
+        let mut expected = configure(0x55, 0x00, 0x20);
+        expected.extend([
+            Op::Command(0x2A),
+            Op::Data(vec![0x00, 0x00, 0x01, 0x3F]),
+            Op::Command(0x2B),
+            Op::Data(vec![0x00, 0x00, 0x01, 0xDF]),
+            Op::Command(0x2C),
+            Op::Data(vec![0x00; 320 * 480 * 2]),
+            Op::Command(0x29),
+        ]);
+        assert_eq!(log.ops(), expected);
+        assert_eq!(display.power_state(), PowerState { sleep: InOut::OUT, display: OnOff::ON, mode: DisplayMode::NORMAL, idle: OnOff::OFF });
+    }
+
+    #[test]
+    fn init_wraps_extended_registers_in_unlock_and_lock() {
+        let (mut display, log) = mock::display();
+        let profile = InitProfile { invctr: Some(0x01), ..InitProfile::NONE };
+        let config = InitConfig::new()
+            .profile(profile)
+            .rotation(Rotation::Deg90)
+            .rgb_order(RgbOrder::BGR)
+            .inversion(true)
+            .fill(None)
+            .display_on(false);
+        display.init(&config).unwrap();
+
+        let mut expected = configure(0x55, 0x68, 0x21);
+        expected.splice(14..14, [
+            Op::Command(0xF0),
+            Op::Data(vec![0xC3]),
+            Op::Command(0xF0),
+            Op::Data(vec![0x96]),
+            Op::Command(0xB4),
+            Op::Data(vec![0x01]),
+            Op::Command(0xF0),
+            Op::Data(vec![0x3C]),
+            Op::Command(0xF0),
+            Op::Data(vec![0x69]),
+        ]);
+        assert_eq!(log.ops(), expected);
+        assert_eq!(display.size(), (480, 320));
+    }
+
+    #[test]
+    fn reads_decode_after_the_dummy_byte() {
+        let (mut display, log) = mock::display();
+        display.interface().reply(&[0xFF, 0x76, 0x80, 0x96]);
+        let id = display.rddid().unwrap();
+        assert_eq!(log.ops(), [Op::Read(0x04, 4)]);
+        assert_eq!(id, InstructionResult::RDDIDReturn(RDDIDResult { lcd_manufacturer_id: 0x76, lcd_driver_version: 0x80, lcd_driver_id: 0x96 }));
+    }
+
+    #[test]
+    fn rddid_round_trips() {
+        let bytes = [0x76, 0x80, 0x96];
+        assert_eq!(RDDIDResult::from_words(&[0xFF, 0x76, 0x80, 0x96]).to_bytes(), bytes);
+    }
+
+    #[test]
+    fn rnedsi_round_trips() {
+        for b in 0..=u8::MAX {
+            assert_eq!(RNEDSIResult::from_words(&[0, b]).to_bytes(), [b]);
+        }
+    }
+
+    #[test]
+    fn rddst_round_trips() {
+        // Every defined bit set, then the reset state
+        let status = [0xFC, 0x5F, 0xA6, 0xE0];
+        assert_eq!(RDDSTResult::from_words(&[0, 0xFC, 0x5F, 0xA6, 0xE0]).to_bytes(), status);
+        let status = [0x00, 0x61, 0x00, 0x00];
+        assert_eq!(RDDSTResult::from_words(&[0, 0x00, 0x61, 0x00, 0x00]).to_bytes(), status);
+    }
+
+    #[test]
+    fn single_byte_status_round_trips() {
+        // Bits each status byte defines; the rest read back as zero
+        for b in 0..=u8::MAX {
+            assert_eq!(RDDPMResult::from_words(&[0, b]).to_bytes(), [b & 0xFC]);
+            assert_eq!(RDDMADCTLResult::from_words(&[0, b]).to_bytes(), [b & 0xFC]);
+            assert_eq!(RDDSMResult::from_words(&[0, b]).to_bytes(), [b & 0xFD]);
+            assert_eq!(RDDSDRResult::from_words(&[0, b]).to_bytes(), [b & 0xC1]);
+        }
+        for b in [0x55, 0x66, 0x77, 0x65, 0x00] {
+            assert_eq!(RDDPIXFMTResult::from_words(&[0, b]).to_bytes(), [b]);
+        }
+        for b in [0x00, 0x01, 0x02, 0x03, 0xA0, 0xA3] {
+            assert_eq!(RDDIMResult::from_words(&[0, b]).to_bytes(), [b]);
+        }
+    }
+}
@@ -0,0 +1,203 @@
+// Test doubles for the driver tests.
+//
+// The display interface, reset pin and delay all append to one shared log,
+// so a test sees bus traffic and waits interleaved in the order the driver
+// issued them.
+extern crate std;
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::vec::Vec;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{ErrorType, OutputPin};
+
+use crate::{DisplayInterface, Error, ST7796S};
+
+// One bus access or wait
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Op {
+    Command(u8),
+    Data(Vec<u8>),
+    Read(u8, usize),
+    Reset(bool),
+    DelayMs(u32),
+    DelayNs(u32),
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Log(Rc<RefCell<Vec<Op>>>);
+
+impl Log {
+    fn push(&self, op: Op) {
+        self.0.borrow_mut().push(op);
+    }
+
+    // Everything logged so far, with back-to-back data writes joined, as a
+    // flood or pixel stream goes out in chunks
+    pub(crate) fn ops(&self) -> Vec<Op> {
+        let mut ops: Vec<Op> = Vec::new();
+        for op in self.0.borrow().iter() {
+            match (ops.last_mut(), op) {
+                (Some(Op::Data(joined)), Op::Data(data)) => joined.extend_from_slice(data),
+                _ => ops.push(op.clone()),
+            }
+        }
+        ops
+    }
+}
+
+// Display interface that logs every access and answers reads from a queue,
+// dummy byte included
+pub(crate) struct MockInterface {
+    log: Log,
+    replies: VecDeque<u8>,
+}
+
+impl MockInterface {
+    // Queue `bytes` to be returned by the following reads
+    pub(crate) fn reply(&mut self, bytes: &[u8]) {
+        self.replies.extend(bytes);
+    }
+}
+
+impl DisplayInterface for MockInterface {
+    type BusError = Infallible;
+    type PinError = Infallible;
+
+    fn write_command(&mut self, cmd: u8) -> Result<(), Error<Infallible, Infallible>> {
+        self.log.push(Op::Command(cmd));
+        Ok(())
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> Result<(), Error<Infallible, Infallible>> {
+        self.log.push(Op::Data(data.to_vec()));
+        Ok(())
+    }
+
+    fn read(&mut self, cmd: u8, data: &mut [u8]) -> Result<(), Error<Infallible, Infallible>> {
+        self.log.push(Op::Read(cmd, data.len()));
+        for b in data.iter_mut() {
+            *b = self.replies.pop_front().unwrap_or(0);
+        }
+        Ok(())
+    }
+}
+
+pub(crate) struct MockPin(Log);
+
+impl ErrorType for MockPin {
+    type Error = Infallible;
+}
+
+impl OutputPin for MockPin {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.0.push(Op::Reset(false));
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.0.push(Op::Reset(true));
+        Ok(())
+    }
+}
+
+pub(crate) struct MockDelay(Log);
+
+impl DelayNs for MockDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        self.0.push(Op::DelayNs(ns));
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        self.0.push(Op::DelayMs(ms));
+    }
+}
+
+pub(crate) type MockDisplay = ST7796S<MockInterface, MockPin, MockDelay>;
+
+// A driver on the mocks, with a reset pin, and the log they share
+pub(crate) fn display() -> (MockDisplay, Log) {
+    let log = Log::default();
+    let interface = MockInterface {
+        log: log.clone(),
+        replies: VecDeque::new(),
+    };
+    let display = ST7796S::new(interface, MockPin(log.clone()), MockDelay(log.clone()));
+    (display, log)
+}
+
+impl MockDisplay {
+    pub(crate) fn interface(&mut self) -> &mut MockInterface {
+        &mut self.interface
+    }
+}
+
+// The same mocks behind the async traits. None of them ever returns
+// `Pending`, so a test can drive the async driver with `block_on`.
+#[cfg(feature = "async")]
+impl crate::asynch::DisplayInterface for MockInterface {
+    type BusError = Infallible;
+    type PinError = Infallible;
+
+    async fn write_command(&mut self, cmd: u8) -> Result<(), Error<Infallible, Infallible>> {
+        DisplayInterface::write_command(self, cmd)
+    }
+
+    async fn write_data(&mut self, data: &[u8]) -> Result<(), Error<Infallible, Infallible>> {
+        DisplayInterface::write_data(self, data)
+    }
+
+    async fn read(&mut self, cmd: u8, data: &mut [u8]) -> Result<(), Error<Infallible, Infallible>> {
+        DisplayInterface::read(self, cmd, data)
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::delay::DelayNs for MockDelay {
+    async fn delay_ns(&mut self, ns: u32) {
+        self.0.push(Op::DelayNs(ns));
+    }
+
+    async fn delay_ms(&mut self, ms: u32) {
+        self.0.push(Op::DelayMs(ms));
+    }
+}
+
+#[cfg(feature = "async")]
+pub(crate) type AsyncMockDisplay = crate::asynch::ST7796S<MockInterface, MockPin, MockDelay>;
+
+// An async driver on the mocks, with a reset pin, and the log they share
+#[cfg(feature = "async")]
+pub(crate) fn async_display() -> (AsyncMockDisplay, Log) {
+    let log = Log::default();
+    let interface = MockInterface {
+        log: log.clone(),
+        replies: VecDeque::new(),
+    };
+    let display = crate::asynch::ST7796S::new(interface, MockPin(log.clone()), MockDelay(log.clone()));
+    (display, log)
+}
+
+// Run a future on the mocks to completion
+#[cfg(feature = "async")]
+pub(crate) fn block_on<F: core::future::Future>(fut: F) -> F::Output {
+    use core::task::{Context, Poll, Waker};
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    let waker = Waker::from(Arc::new(NoopWaker));
+    let mut cx = Context::from_waker(&waker);
+    match core::pin::pin!(fut).poll(&mut cx) {
+        Poll::Ready(out) => out,
+        Poll::Pending => panic!("mock future returned Pending"),
+    }
+}
@@ -0,0 +1,350 @@
+// MCU 8080 parallel bus for the ST7796S, clocked out of an RP2350 PIO state
+// machine.
+//
+// The PIO only drives the data lines and WR. CS, DC and RD stay ordinary GPIO
+// outputs toggled by the CPU between bursts, which keeps the program to two
+// instructions per bus cycle.
+use core::convert::Infallible;
+use core::marker::PhantomData;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::OutputPin;
+
+use rp235x_hal::{
+    dma::{
+        single_buffer,
+        Byte,
+        HalfWord,
+        ReadTarget,
+        SingleChannel,
+        TransferSize,
+    },
+    pio::{
+        InstallError,
+        InstalledProgram,
+        PIOBuilder,
+        PIOExt,
+        PinDir,
+        PinState,
+        Rx,
+        Running,
+        ShiftDirection,
+        StateMachine,
+        StateMachineIndex,
+        Tx,
+        UninitStateMachine,
+        PIO,
+    },
+};
+
+use crate::instruction::Command;
+use crate::{DisplayInterface, Error, ST7796S};
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Width of the 8080 data bus, selected with `IFMODE`/the IM pins on the panel.
+pub trait BusWidth: private::Sealed {
+    /// DMA transfer size matching one bus cycle
+    type Size: TransferSize;
+    /// Number of data lines
+    const BITS: u8;
+    /// Value used to retype the PIO TX FIFO for DMA
+    const SIZE: Self::Size;
+}
+
+/// 8-bit bus on D[7:0]; an RGB565 pixel takes two write cycles.
+pub struct Bus8;
+/// 16-bit bus on D[15:0]; an RGB565 pixel takes one write cycle.
+pub struct Bus16;
+
+impl private::Sealed for Bus8 {}
+impl private::Sealed for Bus16 {}
+
+impl BusWidth for Bus8 {
+    type Size = Byte;
+    const BITS: u8 = 8;
+    const SIZE: Byte = Byte;
+}
+
+impl BusWidth for Bus16 {
+    type Size = HalfWord;
+    const BITS: u8 = 16;
+    const SIZE: HalfWord = HalfWord;
+}
+
+/// GPIO assignment and timing for `Pio8080`.
+///
+/// The data lines must be consecutive GPIOs starting at `data_base`, and all
+/// of them plus `wr` must already be switched to the PIO block's function.
+pub struct Pio8080Config {
+    /// GPIO number of D0
+    pub data_base: u8,
+    /// GPIO number of the WR strobe
+    pub wr: u8,
+    /// PIO clock divisor as (integer, 1/256 fraction). A bus cycle takes two
+    /// PIO clocks and the ST7796S needs at least 66 ns per write cycle, so
+    /// keep the PIO clock at or below 30 MHz.
+    pub clock_divisor: (u16, u8),
+}
+
+type DataWord<W> = <<W as BusWidth>::Size as TransferSize>::Type;
+type FifoTx<P, SM, W> = Tx<(P, SM), <W as BusWidth>::Size>;
+
+/// Write-only 8080 parallel interface driven by a PIO state machine.
+///
+/// Command and parameter bytes are pushed through the FIFO by the CPU, while
+/// `ST7796S::write_pixels_dma` lets a DMA channel feed whole pixel buffers.
+///
+/// The interface cannot read from the panel. RD is parked high and the PIO
+/// program has no read path, so `read` fails with
+/// `Error::UnsupportedCommand`, and with it every driver method that reads
+/// back: `rddid`, `rddst` and the other status reads, `rdtescan`, the
+/// brightness reads and `ramrd`. Keep track of panel state on the host side
+/// instead.
+pub struct Pio8080<P, SM, W, CH, CS, DC, RD>
+where
+    P: PIOExt,
+    SM: StateMachineIndex,
+    W: BusWidth,
+    CH: SingleChannel,
+    CS: OutputPin,
+    DC: OutputPin<Error = CS::Error>,
+    RD: OutputPin<Error = CS::Error>,
+{
+    _sm: StateMachine<(P, SM), Running>,
+    _rx: Rx<(P, SM)>,
+    // Only `None` while a DMA transfer owns the FIFO and channel
+    bus: Option<(FifoTx<P, SM, W>, CH)>,
+    cs: CS,
+    dc: DC,
+    _rd: RD,
+    _width: PhantomData<W>,
+}
+
+impl<P, SM, W, CH, CS, DC, RD> Pio8080<P, SM, W, CH, CS, DC, RD>
+where
+    P: PIOExt,
+    SM: StateMachineIndex,
+    W: BusWidth,
+    CH: SingleChannel,
+    CS: OutputPin,
+    DC: OutputPin<Error = CS::Error>,
+    RD: OutputPin<Error = CS::Error>,
+{
+    /// Install the 8080 write program on `pio`, start it on `sm` and park
+    /// CS, WR and RD high.
+    ///
+    /// Fails with `Error::Bus` if the program doesn't fit into the PIO
+    /// block's instruction memory and with `Error::Pin` if CS or RD can't be
+    /// driven.
+    pub fn new(
+            pio: &mut PIO<P>,
+            sm: UninitStateMachine<(P, SM)>,
+            ch: CH,
+            config: Pio8080Config,
+            mut cs: CS,
+            dc: DC,
+            mut rd: RD) -> Result<Self, Error<InstallError, CS::Error>> {
+        cs.set_high().map_err(Error::Pin)?;
+        rd.set_high().map_err(Error::Pin)?;
+
+        // WR idles high while the machine waits for data, drops with the data
+        // lines and latches them on the rising edge of the next pull.
+        let program = match W::BITS {
+            8 => pio_proc::pio_asm!(
+                ".side_set 1",
+                ".wrap_target",
+                "    pull block  side 1",
+                "    out pins, 8 side 0",
+                ".wrap",
+            ).program,
+            _ => pio_proc::pio_asm!(
+                ".side_set 1",
+                ".wrap_target",
+                "    pull block   side 1",
+                "    out pins, 16 side 0",
+                ".wrap",
+            ).program,
+        };
+        let installed = pio.install(&program).map_err(Error::Bus)?;
+
+        let (int, frac) = config.clock_divisor;
+        let (mut sm, rx, tx) = PIOBuilder::from_installed_program(installed)
+            .out_pins(config.data_base, W::BITS)
+            .side_set_pin_base(config.wr)
+            .out_shift_direction(ShiftDirection::Right)
+            .clock_divisor_fixed_point(int, frac)
+            .build(sm);
+
+        sm.set_pins([(config.wr, PinState::High)]);
+        sm.set_pindirs(
+            (config.data_base..config.data_base + W::BITS)
+                .chain(core::iter::once(config.wr))
+                .map(|pin| (pin, PinDir::Output)),
+        );
+
+        Ok(Pio8080 {
+            _sm: sm.start(),
+            _rx: rx,
+            bus: Some((tx.transfer_size(W::SIZE), ch)),
+            cs,
+            dc,
+            _rd: rd,
+            _width: PhantomData,
+        })
+    }
+
+    /// Wait for the bus to go idle, stop the state machine and return it
+    /// together with the installed program (so it can be uninstalled), the
+    /// DMA channel and the pins.
+    ///
+    /// Hands the interface back unchanged if a DMA transfer still holds the
+    /// FIFO.
+    #[allow(clippy::type_complexity)]
+    pub fn free(mut self) -> Result<(UninitStateMachine<(P, SM)>, InstalledProgram<P>, CH, CS, DC, RD), Self> {
+        if self.wait_idle().is_err() {
+            return Err(self);
+        }
+        match self.bus.take() {
+            Some((tx, ch)) => {
+                let (sm, program) = self._sm.uninit(self._rx, tx);
+                Ok((sm, program, ch, self.cs, self.dc, self._rd))
+            }
+            None => Err(self),
+        }
+    }
+
+    // The TX FIFO, unless a DMA transfer owns it
+    fn tx(&mut self) -> Result<&mut FifoTx<P, SM, W>, Error<Infallible, CS::Error>> {
+        match self.bus.as_mut() {
+            Some((tx, _)) => Ok(tx),
+            None => Err(Error::InvalidState),
+        }
+    }
+
+    // Push one bus cycle worth of data into the FIFO
+    fn push(&mut self, word: u32) -> Result<(), Error<Infallible, CS::Error>> {
+        let tx = self.tx()?;
+        while !tx.write(word) {}
+        Ok(())
+    }
+
+    // Block until the FIFO is drained and the last word has been strobed
+    fn wait_idle(&mut self) -> Result<(), Error<Infallible, CS::Error>> {
+        let tx = self.tx()?;
+        tx.clear_stalled_flag();
+        while !tx.has_stalled() {}
+        Ok(())
+    }
+
+    // Run `f` with CS low and DC at the requested level, waiting for the bus
+    // to go idle before releasing CS
+    fn frame<F>(&mut self, data: bool, f: F) -> Result<(), Error<Infallible, CS::Error>>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error<Infallible, CS::Error>>,
+    {
+        self.wait_idle()?;
+        self.cs.set_low().map_err(Error::Pin)?;
+        let mut res = if data {
+            self.dc.set_high().map_err(Error::Pin)
+        } else {
+            self.dc.set_low().map_err(Error::Pin)
+        };
+        if res.is_ok() {
+            res = f(self).and_then(|()| self.wait_idle());
+        }
+        self.cs.set_high().map_err(Error::Pin)?;
+        res
+    }
+}
+
+impl<P, SM, W, CH, CS, DC, RD> DisplayInterface for Pio8080<P, SM, W, CH, CS, DC, RD>
+where
+    P: PIOExt,
+    SM: StateMachineIndex,
+    W: BusWidth,
+    CH: SingleChannel,
+    CS: OutputPin,
+    DC: OutputPin<Error = CS::Error>,
+    RD: OutputPin<Error = CS::Error>,
+{
+    type BusError = Infallible;
+    type PinError = CS::Error;
+
+    fn write_command(&mut self, cmd: u8) -> Result<(), Error<Infallible, CS::Error>> {
+        self.frame(false, |bus| bus.push(cmd as u32))
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> Result<(), Error<Infallible, CS::Error>> {
+        // Parameters always travel on D[7:0], one byte per write cycle, on
+        // both bus widths
+        self.frame(true, |bus| {
+            for &b in data {
+                bus.push(b as u32)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn read(&mut self, _cmd: u8, _data: &mut [u8]) -> Result<(), Error<Infallible, CS::Error>> {
+        Err(Error::UnsupportedCommand)
+    }
+
+    fn write_pixels<I>(&mut self, pixels: I) -> Result<(), Error<Infallible, CS::Error>>
+    where
+        I: IntoIterator<Item = u16>,
+    {
+        self.frame(true, |bus| {
+            for px in pixels {
+                if W::BITS == 16 {
+                    bus.push(px as u32)?;
+                } else {
+                    bus.push((px >> 8) as u32)?;
+                    bus.push((px & 0xff) as u32)?;
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+impl<P, SM, W, CH, CS, DC, RD, RST, T, TE> ST7796S<Pio8080<P, SM, W, CH, CS, DC, RD>, RST, T, TE>
+where
+    P: PIOExt,
+    SM: StateMachineIndex,
+    W: BusWidth,
+    CH: SingleChannel,
+    CS: OutputPin,
+    DC: OutputPin<Error = CS::Error>,
+    RD: OutputPin<Error = CS::Error>,
+    RST: OutputPin<Error = CS::Error>,
+    T: DelayNs,
+{
+    /// Stream a pixel buffer into the current window over DMA and block until
+    /// the last word has been strobed onto the bus.
+    ///
+    /// Sends RAMWR first, so call `set_window` beforehand. Each buffer word is
+    /// one bus cycle: big-endian byte pairs on `Bus8`, whole RGB565 pixels on
+    /// `Bus16`. The buffer is handed back once the transfer is complete.
+    pub fn write_pixels_dma<B>(&mut self, buf: B) -> Result<B, Error<Infallible, CS::Error>>
+    where
+        B: ReadTarget<ReceivedWord = DataWord<W>>,
+    {
+        self.write_command(Command::RAMWR.into())?;
+
+        let bus = &mut self.interface;
+        bus.wait_idle()?;
+        bus.cs.set_low().map_err(Error::Pin)?;
+        bus.dc.set_high().map_err(Error::Pin)?;
+
+        let (tx, ch) = bus.bus.take().ok_or(Error::InvalidState)?;
+        let (ch, buf, tx) = single_buffer::Config::new(ch, buf, tx).start().wait();
+        bus.bus = Some((tx, ch));
+
+        let res = bus.wait_idle();
+        bus.cs.set_high().map_err(Error::Pin)?;
+        res.map(|()| buf)
+    }
+}
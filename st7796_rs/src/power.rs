@@ -0,0 +1,231 @@
+// Power supply and VCOM registers of the extended command set (PWCTR1-3,
+// VCMPCTR, VCMOFFS).
+//
+// Contrast and flicker vary between panel batches mostly through VCOM and
+// the gamma reference voltages. Voltages are given in millivolts and
+// converted to the nearest register step; anything outside the range the
+// datasheet tabulates is rejected with `Error::InvalidParameter`.
+use embedded_hal::digital::OutputPin;
+
+use crate::command::{Rejected, Shadow, Transaction};
+use crate::instruction::Command;
+use crate::{DisplayInterface, Error, InstructionResult, ST7796S};
+
+// PWCTR1 tables, in register code order
+const AVDD_MV: [i32; 4] = [6200, 6400, 6600, 6800];
+const AVCL_MV: [i32; 4] = [-4400, -4600, -4800, -5000];
+const VGH_MV: [i32; 8] = [12541, 12889, 13257, 13647, 14061, 14500, 14968, 15467];
+const VGL_MV: [i32; 8] = [-7158, -7667, -8235, -8875, -9600, -10429, -11385, -12500];
+
+// Code of the `table` entry nearest to `mv`, or `None` if `mv` lies outside
+// the table. Tables are monotonic, rising or falling.
+fn table_code(mv: i32, table: &[i32]) -> Option<u8> {
+    let (first, last) = (table[0], table[table.len() - 1]);
+    if mv < first.min(last) || mv > first.max(last) {
+        return None;
+    }
+    let (code, _) = table
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, v)| (mv - **v).abs())?;
+    Some(code as u8)
+}
+
+// Code of the step nearest to `mv` on a linear scale starting at `min`, or
+// `None` if `mv` lies outside `min..=max`
+fn step_code(mv: i32, min: i32, max: i32, step: i32) -> Option<u8> {
+    if mv < min || mv > max {
+        return None;
+    }
+    Some(((mv - min + step / 2) / step) as u8)
+}
+
+/// Charge pump output voltages set by PWCTR1, in millivolts.
+///
+/// AVDD runs from 6200 to 6800 in 200 mV steps, AVCL from -4400 to -5000,
+/// VGH from 12541 to 15467 and VGL from -7158 to -12500 in the uneven steps
+/// of the datasheet table. The defaults are the panel's reset values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PowerControl1 {
+    pub avdd_mv: u16,
+    pub avcl_mv: i16,
+    pub vgh_mv: u16,
+    pub vgl_mv: i16,
+}
+
+impl Default for PowerControl1 {
+    fn default() -> Self {
+        PowerControl1 {
+            avdd_mv: 6600,
+            avcl_mv: -4400,
+            vgh_mv: 13257,
+            vgl_mv: -10429,
+        }
+    }
+}
+
+impl PowerControl1 {
+    // PWCTR1 parameter bytes, or `None` if a voltage is out of range
+    pub(crate) fn to_bytes(self) -> Option<[u8; 2]> {
+        let avdd = table_code(self.avdd_mv as i32, &AVDD_MV)?;
+        let avcl = table_code(self.avcl_mv as i32, &AVCL_MV)?;
+        let vgh = table_code(self.vgh_mv as i32, &VGH_MV)?;
+        let vgl = table_code(self.vgl_mv as i32, &VGL_MV)?;
+        Some([(avdd << 6) | (avcl << 4), (vgh << 4) | vgl])
+    }
+}
+
+/// Source or gamma driver current level (PWCTR3 SOP/GOP).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DriveCurrent {
+    OFF,
+    LOW,
+    MEDIUM,
+    HIGH,
+}
+
+impl DriveCurrent {
+    fn bits(self) -> u8 {
+        match self {
+            DriveCurrent::OFF => 0,
+            DriveCurrent::LOW => 1,
+            DriveCurrent::MEDIUM => 2,
+            DriveCurrent::HIGH => 3,
+        }
+    }
+}
+
+// PWCTR2 VRH code for GVDD `gvdd_mv` above VCOM
+pub(crate) fn pwctr2_byte(gvdd_mv: u16) -> Option<u8> {
+    step_code(gvdd_mv as i32, 3550, 5500, 50)
+}
+
+// PWCTR3 byte. D7-D4 have no function but must read 1010b: command table 2
+// (datasheet p.138) lists them as fixed and the Sitronix reference init
+// writes 0xA7.
+pub(crate) fn pwctr3_byte(source: DriveCurrent, gamma: DriveCurrent) -> u8 {
+    0xA0 | (source.bits() << 2) | gamma.bits()
+}
+
+// VCMPCTR VCMP code for `vcom_mv`
+pub(crate) fn vcmpctr_byte(vcom_mv: u16) -> Option<u8> {
+    step_code(vcom_mv as i32, 300, 1875, 25)
+}
+
+// VCMOFFS byte: VMFSEL plus the offset in two's complement 25 mV steps, or
+// VMFSEL clear to use the NV memory offset
+pub(crate) fn vcmoffs_byte(offset_mv: Option<i16>) -> Option<u8> {
+    match offset_mv {
+        Some(mv) => {
+            let steps = step_code(mv as i32, -800, 775, 25)? as i8 - 32;
+            Some(0x80 | (steps as u8 & 0x3F))
+        }
+        None => Some(0x00),
+    }
+}
+
+impl Shadow {
+    pub(crate) fn pwctr1(&self, power: PowerControl1) -> Result<Transaction, Rejected> {
+        let bytes = power.to_bytes().ok_or(Rejected::InvalidParameter)?;
+        Ok(self.keep().extended(Command::PWCTR1, &bytes))
+    }
+
+    pub(crate) fn pwctr2(&self, gvdd_mv: u16) -> Result<Transaction, Rejected> {
+        let vrh = pwctr2_byte(gvdd_mv).ok_or(Rejected::InvalidParameter)?;
+        Ok(self.keep().extended(Command::PWCTR2, &[vrh]))
+    }
+
+    pub(crate) fn pwctr3(&self, source: DriveCurrent, gamma: DriveCurrent) -> Transaction {
+        self.keep().extended(Command::PWCTR3, &[pwctr3_byte(source, gamma)])
+    }
+
+    pub(crate) fn vcmpctr(&self, vcom_mv: u16) -> Result<Transaction, Rejected> {
+        let vcmp = vcmpctr_byte(vcom_mv).ok_or(Rejected::InvalidParameter)?;
+        Ok(self.keep().extended(Command::VCMPCTR, &[vcmp]))
+    }
+
+    pub(crate) fn vcmoffs(&self, offset_mv: Option<i16>) -> Result<Transaction, Rejected> {
+        let byte = vcmoffs_byte(offset_mv).ok_or(Rejected::InvalidParameter)?;
+        Ok(self.keep().extended(Command::VCMOFFS, &[byte]))
+    }
+}
+
+impl<DI, RST, T, TE> ST7796S<DI, RST, T, TE>
+where
+    DI: DisplayInterface,
+    RST: OutputPin<Error = DI::PinError>,
+{
+    /// Set the AVDD/AVCL and VGH/VGL charge pumps (PWCTR1).
+    pub fn pwctr1(&mut self, power: PowerControl1) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.pwctr1(power)?)
+    }
+
+    /// Set GVDD, the positive gamma reference, as `gvdd_mv` above VCOM
+    /// (PWCTR2). GVCL mirrors it below VCOM.
+    ///
+    /// Accepts 3550 to 5500 mV in 50 mV steps.
+    pub fn pwctr2(&mut self, gvdd_mv: u16) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.pwctr2(gvdd_mv)?)
+    }
+
+    /// Set the source and gamma driver currents in normal mode (PWCTR3).
+    pub fn pwctr3(&mut self, source: DriveCurrent, gamma: DriveCurrent) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.pwctr3(source, gamma))
+    }
+
+    /// Set the VCOM voltage (VCMPCTR).
+    ///
+    /// Accepts 300 to 1875 mV in 25 mV steps. VCOM plus the VCMOFFS offset
+    /// must stay below 1875 mV.
+    pub fn vcmpctr(&mut self, vcom_mv: u16) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.vcmpctr(vcom_mv)?)
+    }
+
+    /// Trim VCOM by `offset_mv` (VCMOFFS), or go back to the offset stored
+    /// in the panel's NV memory with `None`.
+    ///
+    /// Accepts -800 to 775 mV in 25 mV steps.
+    pub fn vcmoffs(&mut self, offset_mv: Option<i16>) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.vcmoffs(offset_mv)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pwctr2_steps_of_50_mv() {
+        assert_eq!(pwctr2_byte(3550), Some(0x00));
+        assert_eq!(pwctr2_byte(4500), Some(0x13));
+        assert_eq!(pwctr2_byte(5500), Some(0x27));
+        // Rounds to the nearest step
+        assert_eq!(pwctr2_byte(3574), Some(0x00));
+        assert_eq!(pwctr2_byte(3575), Some(0x01));
+        assert_eq!(pwctr2_byte(3549), None);
+        assert_eq!(pwctr2_byte(5501), None);
+    }
+
+    #[test]
+    fn vcmpctr_steps_of_25_mv() {
+        assert_eq!(vcmpctr_byte(300), Some(0x00));
+        assert_eq!(vcmpctr_byte(1000), Some(0x1C));
+        assert_eq!(vcmpctr_byte(1875), Some(0x3F));
+        assert_eq!(vcmpctr_byte(299), None);
+        assert_eq!(vcmpctr_byte(1876), None);
+    }
+
+    #[test]
+    fn vcmoffs_is_twos_complement_with_vmfsel() {
+        assert_eq!(vcmoffs_byte(None), Some(0x00));
+        assert_eq!(vcmoffs_byte(Some(0)), Some(0x80));
+        assert_eq!(vcmoffs_byte(Some(25)), Some(0x81));
+        assert_eq!(vcmoffs_byte(Some(-25)), Some(0xBF));
+        assert_eq!(vcmoffs_byte(Some(775)), Some(0x9F));
+        assert_eq!(vcmoffs_byte(Some(-800)), Some(0xA0));
+        assert_eq!(vcmoffs_byte(Some(800)), None);
+        assert_eq!(vcmoffs_byte(Some(-825)), None);
+    }
+}
@@ -0,0 +1,187 @@
+// Hardware vertical scrolling (VSCRDER/VSCRSADD).
+//
+// The panel splits its 480 frame memory rows into a top fixed area, a
+// scrolling area and a bottom fixed area. Setting the scroll start address
+// rotates which frame memory row is shown at the top of the scrolling area,
+// so a log view or ticker only has to redraw the newly exposed rows.
+use embedded_hal::digital::OutputPin;
+
+use crate::command::{Rejected, Shadow, Transaction};
+use crate::config::HEIGHT;
+use crate::instruction::Command;
+use crate::{DisplayInterface, Error, InstructionResult, ST7796S};
+
+/// Vertical scrolling layout and current start address.
+///
+/// All rows are frame memory rows in the panel's native portrait scan with
+/// MADCTL ML=0. Built with `new` and `with_start`, so the areas always add up
+/// to the panel height and the start address lies inside the scrolling area.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ScrollArea {
+    top_fixed: u16,
+    scroll_height: u16,
+    bottom_fixed: u16,
+    start: u16,
+}
+
+impl ScrollArea {
+    /// The whole panel scrolls, starting at row 0; the reset state.
+    pub const FULL: ScrollArea = ScrollArea {
+        top_fixed: 0,
+        scroll_height: HEIGHT,
+        bottom_fixed: 0,
+        start: 0,
+    };
+
+    /// A layout with the start address at the top of the scrolling area, or
+    /// `None` unless the three areas add up to the 480 panel rows and the
+    /// scrolling area is non-empty.
+    pub fn new(top_fixed: u16, scroll_height: u16, bottom_fixed: u16) -> Option<Self> {
+        let total = top_fixed as u32 + scroll_height as u32 + bottom_fixed as u32;
+        if scroll_height == 0 || total != HEIGHT as u32 {
+            return None;
+        }
+        Some(ScrollArea {
+            top_fixed,
+            scroll_height,
+            bottom_fixed,
+            start: top_fixed,
+        })
+    }
+
+    /// The same layout scrolled to `start`, or `None` if `start` lies outside
+    /// the scrolling area.
+    pub fn with_start(self, start: u16) -> Option<Self> {
+        if start < self.top_fixed || start >= self.top_fixed + self.scroll_height {
+            return None;
+        }
+        Some(ScrollArea { start, ..self })
+    }
+
+    /// Rows fixed at the top of the panel.
+    pub fn top_fixed(&self) -> u16 {
+        self.top_fixed
+    }
+
+    /// Rows in the scrolling area.
+    pub fn scroll_height(&self) -> u16 {
+        self.scroll_height
+    }
+
+    /// Rows fixed at the bottom of the panel.
+    pub fn bottom_fixed(&self) -> u16 {
+        self.bottom_fixed
+    }
+
+    /// Frame memory row shown at the top of the scrolling area.
+    pub fn start(&self) -> u16 {
+        self.start
+    }
+
+    // The same layout with scrolling ended, i.e. the start address back at
+    // the top of the scrolling area
+    pub(crate) fn unscrolled(self) -> Self {
+        ScrollArea { start: self.top_fixed, ..self }
+    }
+
+    /// The frame memory row that currently appears on panel row `row`.
+    ///
+    /// Draw into this row to make something show up at `row` on screen.
+    pub fn physical_row(&self, row: u16) -> u16 {
+        let top = self.top_fixed;
+        if row < top || row >= top + self.scroll_height {
+            return row;
+        }
+        top + (row - top + self.start - top) % self.scroll_height
+    }
+
+    // VSCRDER parameter bytes
+    pub(crate) fn vscrder_bytes(&self) -> [u8; 6] {
+        let [tfa_h, tfa_l] = self.top_fixed.to_be_bytes();
+        let [vsa_h, vsa_l] = self.scroll_height.to_be_bytes();
+        let [bfa_h, bfa_l] = self.bottom_fixed.to_be_bytes();
+        [tfa_h, tfa_l, vsa_h, vsa_l, bfa_h, bfa_l]
+    }
+}
+
+impl Default for ScrollArea {
+    fn default() -> Self {
+        ScrollArea::FULL
+    }
+}
+
+impl Shadow {
+    // VSCRDER for `area`, then VSCRSADD back to the top of its scrolling area
+    pub(crate) fn vscrder(&self, top_fixed: u16, scroll_height: u16, bottom_fixed: u16) -> Result<Transaction, Rejected> {
+        let area = ScrollArea::new(top_fixed, scroll_height, bottom_fixed).ok_or(Rejected::InvalidParameter)?;
+        Ok(Transaction::new(Shadow { scroll: area, ..*self })
+            .write(Command::VSCRDER, &area.vscrder_bytes())
+            .write(Command::VSCRSADD, &area.start.to_be_bytes()))
+    }
+
+    pub(crate) fn vscrsadd(&self, start: u16) -> Result<Transaction, Rejected> {
+        let scroll = self.scroll.with_start(start).ok_or(Rejected::InvalidParameter)?;
+        Ok(Transaction::new(Shadow { scroll, ..*self }).write(Command::VSCRSADD, &start.to_be_bytes()))
+    }
+}
+
+impl<DI, RST, T, TE> ST7796S<DI, RST, T, TE>
+where
+    DI: DisplayInterface,
+    RST: OutputPin<Error = DI::PinError>,
+{
+    /// Define the top fixed, scrolling and bottom fixed areas (VSCRDER).
+    ///
+    /// The three heights must add up to 480 rows. The start address moves
+    /// back to the top of the new scrolling area.
+    pub fn vscrder(&mut self, top_fixed: u16, scroll_height: u16, bottom_fixed: u16) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.vscrder(top_fixed, scroll_height, bottom_fixed)?)
+    }
+
+    /// Set the frame memory row shown at the top of the scrolling area
+    /// (VSCRSADD).
+    pub fn vscrsadd(&mut self, start: u16) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.vscrsadd(start)?)
+    }
+
+    /// Current scrolling layout, for mapping screen rows to frame memory rows.
+    pub fn scroll_area(&self) -> ScrollArea {
+        self.shadow.scroll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn physical_row_without_scrolling() {
+        for row in [0, 1, 240, 479] {
+            assert_eq!(ScrollArea::FULL.physical_row(row), row);
+        }
+    }
+
+    #[test]
+    fn physical_row_wraps_inside_the_scrolling_area() {
+        // 40 fixed rows on top, 400 scrolling, 40 fixed at the bottom
+        let area = ScrollArea::new(40, 400, 40).unwrap().with_start(100).unwrap();
+        assert_eq!(area.physical_row(0), 0);
+        assert_eq!(area.physical_row(39), 39);
+        assert_eq!(area.physical_row(40), 100);
+        assert_eq!(area.physical_row(379), 439);
+        assert_eq!(area.physical_row(380), 40);
+        assert_eq!(area.physical_row(439), 99);
+        assert_eq!(area.physical_row(440), 440);
+        assert_eq!(area.physical_row(479), 479);
+    }
+
+    #[test]
+    fn areas_must_cover_the_panel() {
+        assert_eq!(ScrollArea::new(0, 0, 480), None);
+        assert_eq!(ScrollArea::new(40, 400, 41), None);
+        let area = ScrollArea::new(40, 400, 40).unwrap();
+        assert_eq!(area.with_start(39), None);
+        assert_eq!(area.with_start(440), None);
+    }
+}
@@ -0,0 +1,140 @@
+// Tearing effect output (TEON/TEOFF/STE/GSCAN).
+//
+// The panel refreshes from frame memory top to bottom at the FRMCTR1 rate.
+// Writing GRAM while the refresh passes over the same rows shows half of the
+// old frame and half of the new one. With TE enabled the TE pin goes high
+// during vertical blanking (or when the refresh reaches the STE scanline), so
+// a flush that starts on that rising edge stays ahead of the refresh.
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{InputPin, OutputPin};
+
+use crate::command::{Rejected, Shadow, Transaction};
+use crate::config::HEIGHT;
+use crate::instruction::Command;
+use crate::{DisplayInterface, Error, InOut, InstructionResult, TearingEffect, ST7796S};
+
+/// How long `wait_for_te` waits for a TE edge before giving up, in
+/// microseconds: two frame periods at 50 Hz.
+pub const TE_TIMEOUT_US: u32 = 40_000;
+
+// Interval between TE pin samples while waiting for an edge
+const TE_POLL_US: u32 = 10;
+
+impl TearingEffect {
+    // TEON parameter byte (TEM bit)
+    pub(crate) fn teon_byte(self) -> u8 {
+        match self {
+            TearingEffect::MODE1 => 0x00,
+            TearingEffect::MODE2 => 0x01,
+        }
+    }
+}
+
+impl Shadow {
+    pub(crate) fn teon(&self, mode: TearingEffect) -> Transaction {
+        Transaction::new(Shadow { tearing: Some(mode), ..*self }).write(Command::TEON, &[mode.teon_byte()])
+    }
+
+    pub(crate) fn teoff(&self) -> Transaction {
+        Transaction::new(Shadow { tearing: None, ..*self }).write(Command::TEOFF, &[])
+    }
+
+    pub(crate) fn tescan(&self, line: u16) -> Result<Transaction, Rejected> {
+        if line >= HEIGHT {
+            return Err(Rejected::InvalidParameter);
+        }
+        Ok(Transaction::new(Shadow { tearing: Some(TearingEffect::MODE1), ..*self })
+            .write(Command::TESCAN, &line.to_be_bytes()))
+    }
+
+    // The scan line is N[9:0]; mask off whatever the panel sends above it
+    pub(crate) fn rdtescan(&self) -> Transaction {
+        self.keep().read(Command::RDTESCAN, 3, |w| InstructionResult::RDTESCANReturn(u16::from_be_bytes([w[1], w[2]]) & 0x03FF))
+    }
+
+    // Whether TE is on and the panel awake, i.e. the TE pin toggles
+    pub(crate) fn te_running(&self) -> Result<(), Rejected> {
+        if self.tearing.is_none() || self.power.sleep == InOut::IN {
+            warn!("TE wait rejected: TE off or in sleep-in");
+            return Err(Rejected::InvalidState);
+        }
+        Ok(())
+    }
+}
+
+impl<DI, RST, T, TE> ST7796S<DI, RST, T, TE>
+where
+    DI: DisplayInterface,
+    RST: OutputPin<Error = DI::PinError>,
+{
+    /// Turn the TE output on (TEON).
+    ///
+    /// `MODE1` pulses once per frame during vertical blanking; `MODE2` also
+    /// pulses on every horizontal blanking period.
+    pub fn teon(&mut self, mode: TearingEffect) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.teon(mode))
+    }
+
+    // Turn the TE output off
+    pub fn teoff(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.teoff())
+    }
+
+    /// Raise TE when the refresh reaches frame memory row `line` (STE), in
+    /// mode 1. Takes effect from the next frame.
+    ///
+    /// Line 0 is the same as `teon(TearingEffect::MODE1)`.
+    pub fn tescan(&mut self, line: u16) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.tescan(line)?)
+    }
+
+    // Read the frame memory row the refresh is currently on
+    pub fn rdtescan(&mut self) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.rdtescan())
+    }
+
+    /// TE mode the driver last enabled, or `None` while TE is off.
+    pub fn tearing_effect(&self) -> Option<TearingEffect> {
+        self.shadow.tearing
+    }
+}
+
+impl<DI, RST, T, TE> ST7796S<DI, RST, T, TE>
+where
+    DI: DisplayInterface,
+    RST: OutputPin<Error = DI::PinError>,
+    T: DelayNs,
+    TE: InputPin<Error = DI::PinError>,
+{
+    /// Block until the next rising edge on the TE pin attached with
+    /// `with_te_pin`.
+    ///
+    /// Call this right before a flush so GRAM writes start in vertical
+    /// blanking, or at the `tescan` line. In `MODE2` every line produces an
+    /// edge, so use `MODE1` or `tescan` for frame sync. Fails with
+    /// `Error::InvalidState` while TE is off or the panel is asleep, as the
+    /// pin never toggles then, and with `Error::Timeout` if no edge arrives
+    /// within `TE_TIMEOUT_US`.
+    pub fn wait_for_te(&mut self) -> Result<(), Error<DI::BusError, DI::PinError>> {
+        self.shadow.te_running()?;
+        let mut waited = 0;
+        while self.te.is_high().map_err(Error::Pin)? {
+            waited = self.te_tick(waited)?;
+        }
+        while self.te.is_low().map_err(Error::Pin)? {
+            waited = self.te_tick(waited)?;
+        }
+        trace!("TE edge");
+        Ok(())
+    }
+
+    // Wait one poll interval, or fail once the TE budget is spent
+    fn te_tick(&mut self, waited: u32) -> Result<u32, Error<DI::BusError, DI::PinError>> {
+        if waited >= TE_TIMEOUT_US {
+            warn!("TE wait timed out");
+            return Err(Error::Timeout);
+        }
+        self.timer.delay_us(TE_POLL_US);
+        Ok(waited + TE_POLL_US)
+    }
+}
@@ -0,0 +1,354 @@
+// Panel timing registers of the extended command set (FRMCTR1/3, INVCTR, BPC,
+// DFC, EM).
+//
+// These set how fast the panel refreshes and how it drives the glass, so they
+// decide both the SPI bandwidth a flicker-free frame needs and whether a
+// given module flickers at all. The datasheet documents no way to read them
+// back; the panel only reports the Table 1 state through RDDST and friends.
+use embedded_hal::digital::OutputPin;
+
+use crate::command::{Rejected, Shadow, Transaction};
+use crate::config::{FrameRate, PartialFrameRate, HEIGHT};
+use crate::instruction::Command;
+use crate::{bit, AddressOrder, DisplayInterface, Error, InstructionResult, ST7796S};
+
+/// Source driving pattern selected by INVCTR (DINV[1:0]).
+///
+/// The default is 1-dot inversion, the panel's reset value.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InversionMode {
+    COLUMN,
+    #[default]
+    DOT1,
+    DOT2,
+}
+
+impl InversionMode {
+    // INVCTR parameter byte
+    pub(crate) fn to_bytes(self) -> [u8; 1] {
+        match self {
+            InversionMode::COLUMN => [0x00],
+            InversionMode::DOT1 => [0x01],
+            InversionMode::DOT2 => [0x02],
+        }
+    }
+}
+
+/// Blanking porches set by BPC.
+///
+/// Vertical porches are in lines, the horizontal back porch in DOTCLK
+/// cycles; each must be between 2 and 255. The defaults are the panel's
+/// reset values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BlankingPorch {
+    pub vfp: u8,                      // Vertical front porch
+    pub vbp: u8,                      // Vertical back porch
+    pub hbp: u8,                      // Horizontal back porch
+}
+
+impl Default for BlankingPorch {
+    fn default() -> Self {
+        BlankingPorch {
+            vfp: 0x02,
+            vbp: 0x02,
+            hbp: 0x04,
+        }
+    }
+}
+
+impl BlankingPorch {
+    // BPC parameter bytes, or `None` if a porch uses a reserved value
+    pub(crate) fn to_bytes(self) -> Option<[u8; 4]> {
+        if self.vfp < 2 || self.vbp < 2 || self.hbp < 2 {
+            return None;
+        }
+        Some([self.vfp, self.vbp, 0x00, self.hbp])
+    }
+}
+
+/// Source output on the non-display area in partial mode (DFC PT[1:0]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NonDisplayLevel {
+    V63,
+    V0,
+}
+
+/// Display function control (DFC).
+///
+/// The defaults are the panel's reset values (80h 02h 3Bh): bypass set, so RGB
+/// interface data goes straight to the shift register, normal scan of the
+/// non-display area, G1 to G480 and S1 to S960, a 5 frame interval scan cycle
+/// and all 480 lines driven. The first byte only matters on the RGB
+/// interface; SPI and 8080 writes always go through GRAM.
+///
+/// `isc` selects an interval scan cycle of `2 * isc + 1` frames, so 1 to 15
+/// give the odd frame counts 3 to 31 the datasheet allows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DisplayFunction {
+    pub bypass: bool,                 // RGB data straight to the shift register
+    pub rcm: bool,                    // RGB interface in SYNC instead of DE mode
+    pub rm: bool,                     // GRAM written over the RGB interface
+    pub interval_scan: bool,          // Interval scan in the non-display area
+    pub non_display: NonDisplayLevel, // Source output in the non-display area
+    pub gate_scan: AddressOrder,      // GS: gate scan G1->G480 or G480->G1
+    pub source_scan: AddressOrder,    // SS: source scan S1->S960 or S960->S1
+    pub sm: bool,                     // Interlaced gate pin arrangement
+    pub isc: u8,                      // Interval scan cycle ISC[3:0], 1 to 15
+    pub lines: u16,                   // Driven lines, a multiple of 8 up to 480
+}
+
+impl Default for DisplayFunction {
+    fn default() -> Self {
+        DisplayFunction {
+            bypass: true,
+            rcm: false,
+            rm: false,
+            interval_scan: false,
+            non_display: NonDisplayLevel::V63,
+            gate_scan: AddressOrder::INCREMENT,
+            source_scan: AddressOrder::INCREMENT,
+            sm: false,
+            isc: 0x02,
+            lines: HEIGHT,
+        }
+    }
+}
+
+impl DisplayFunction {
+    /// Length of the interval scan cycle in frames, `2 * isc + 1`, or `None`
+    /// if `isc` is outside 1 to 15.
+    pub fn scan_cycle_frames(&self) -> Option<u8> {
+        (1..=0x0F).contains(&self.isc).then(|| 2 * self.isc + 1)
+    }
+
+    // DFC parameter bytes, or `None` if ISC or the line count is out of range
+    pub(crate) fn to_bytes(self) -> Option<[u8; 3]> {
+        if self.isc == 0 || self.isc > 0x0F || self.lines == 0 || self.lines > HEIGHT || self.lines % 8 != 0 {
+            return None;
+        }
+        let pt = match self.non_display {
+            NonDisplayLevel::V63 => 0x00,
+            NonDisplayLevel::V0 => 0x01,
+        };
+        Some([
+            bit(self.bypass, 0x80) | bit(self.rcm, 0x40) | bit(self.rm, 0x20) | bit(self.interval_scan, 0x08) | pt,
+            bit(self.gate_scan == AddressOrder::DECREMENT, 0x40)
+                | bit(self.source_scan == AddressOrder::DECREMENT, 0x20)
+                | bit(self.sm, 0x10)
+                | self.isc,
+            (self.lines / 8 - 1) as u8,
+        ])
+    }
+}
+
+/// How 16-bit pixels are widened to the 18-bit GRAM (EM EPF[1:0]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ColorExpansion {
+    /// Red and blue LSB set to 0
+    ZERO,
+    /// Red and blue LSB set to 1
+    ONE,
+    /// Red and blue LSB copied from their MSB
+    MSB,
+    /// Red and blue LSB copied from the green LSB
+    GREEN,
+}
+
+/// Gate driver output level (EM GON/DTE).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GateOutput {
+    VGH,
+    VGL,
+    NORMAL,
+}
+
+/// Entry mode (EM).
+///
+/// Deep standby (DSTB) is always left off: it drops GRAM and every register
+/// and can only be left with a reset or a CSX pulse train.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct EntryMode {
+    pub expansion: ColorExpansion,
+    pub gate_output: GateOutput,
+}
+
+impl Default for EntryMode {
+    fn default() -> Self {
+        EntryMode {
+            expansion: ColorExpansion::ZERO,
+            gate_output: GateOutput::NORMAL,
+        }
+    }
+}
+
+impl EntryMode {
+    // EM parameter byte
+    pub(crate) fn to_bytes(self) -> [u8; 1] {
+        let epf = match self.expansion {
+            ColorExpansion::ZERO => 0x00,
+            ColorExpansion::ONE => 0x40,
+            ColorExpansion::MSB => 0x80,
+            ColorExpansion::GREEN => 0xC0,
+        };
+        let gate = match self.gate_output {
+            GateOutput::VGH => 0x00,
+            GateOutput::VGL => 0x04,
+            GateOutput::NORMAL => 0x06,
+        };
+        [epf | gate]
+    }
+}
+
+impl Shadow {
+    pub(crate) fn frmctr1(&self, frame_rate: FrameRate) -> Result<Transaction, Rejected> {
+        let bytes = frame_rate.to_bytes().ok_or(Rejected::InvalidParameter)?;
+        Ok(self.keep().extended(Command::FRMCTR1, &bytes))
+    }
+
+    pub(crate) fn frmctr3(&self, frame_rate: PartialFrameRate) -> Result<Transaction, Rejected> {
+        let bytes = frame_rate.to_bytes().ok_or(Rejected::InvalidParameter)?;
+        Ok(self.keep().extended(Command::FRMCTR3, &bytes))
+    }
+
+    pub(crate) fn invctr(&self, mode: InversionMode) -> Transaction {
+        self.keep().extended(Command::INVCTR, &mode.to_bytes())
+    }
+
+    pub(crate) fn bpc(&self, porch: BlankingPorch) -> Result<Transaction, Rejected> {
+        let bytes = porch.to_bytes().ok_or(Rejected::InvalidParameter)?;
+        Ok(self.keep().extended(Command::BPC, &bytes))
+    }
+
+    pub(crate) fn dfc(&self, function: DisplayFunction) -> Result<Transaction, Rejected> {
+        let bytes = function.to_bytes().ok_or(Rejected::InvalidParameter)?;
+        Ok(self.keep().extended(Command::DFC, &bytes))
+    }
+
+    pub(crate) fn em(&self, mode: EntryMode) -> Transaction {
+        self.keep().extended(Command::EM, &mode.to_bytes())
+    }
+}
+
+impl<DI, RST, T, TE> ST7796S<DI, RST, T, TE>
+where
+    DI: DisplayInterface,
+    RST: OutputPin<Error = DI::PinError>,
+{
+    /// Set the normal mode frame rate (FRMCTR1).
+    pub fn frmctr1(&mut self, frame_rate: FrameRate) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.frmctr1(frame_rate)?)
+    }
+
+    /// Set the partial mode frame rate (FRMCTR3).
+    pub fn frmctr3(&mut self, frame_rate: PartialFrameRate) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.frmctr3(frame_rate)?)
+    }
+
+    /// Select column, 1-dot or 2-dot inversion (INVCTR).
+    pub fn invctr(&mut self, mode: InversionMode) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.invctr(mode))
+    }
+
+    /// Set the blanking porches (BPC).
+    pub fn bpc(&mut self, porch: BlankingPorch) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.bpc(porch)?)
+    }
+
+    /// Set scan directions, driven lines and RGB interface options (DFC).
+    pub fn dfc(&mut self, function: DisplayFunction) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.dfc(function)?)
+    }
+
+    /// Set the 16- to 18-bit colour expansion and gate output level (EM).
+    pub fn em(&mut self, mode: EntryMode) -> Result<InstructionResult, Error<DI::BusError, DI::PinError>> {
+        self.send(self.shadow.em(mode))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invctr_selects_dinv() {
+        assert_eq!(InversionMode::COLUMN.to_bytes(), [0x00]);
+        assert_eq!(InversionMode::DOT1.to_bytes(), [0x01]);
+        assert_eq!(InversionMode::DOT2.to_bytes(), [0x02]);
+    }
+
+    #[test]
+    fn bpc_defaults_match_reset_values() {
+        assert_eq!(BlankingPorch::default().to_bytes(), Some([0x02, 0x02, 0x00, 0x04]));
+        let porch = BlankingPorch { vfp: 0xFF, vbp: 0x10, hbp: 0x02 };
+        assert_eq!(porch.to_bytes(), Some([0xFF, 0x10, 0x00, 0x02]));
+    }
+
+    #[test]
+    fn bpc_rejects_reserved_porches() {
+        let porch = BlankingPorch::default();
+        assert_eq!(BlankingPorch { vfp: 1, ..porch }.to_bytes(), None);
+        assert_eq!(BlankingPorch { vbp: 0, ..porch }.to_bytes(), None);
+        assert_eq!(BlankingPorch { hbp: 1, ..porch }.to_bytes(), None);
+    }
+
+    #[test]
+    fn dfc_defaults_match_reset_values() {
+        assert_eq!(DisplayFunction::default().to_bytes(), Some([0x80, 0x02, 0x3B]));
+    }
+
+    #[test]
+    fn dfc_packs_every_field() {
+        let function = DisplayFunction {
+            bypass: false,
+            rcm: true,
+            rm: true,
+            interval_scan: true,
+            non_display: NonDisplayLevel::V0,
+            gate_scan: AddressOrder::DECREMENT,
+            source_scan: AddressOrder::DECREMENT,
+            sm: true,
+            isc: 0x0F,
+            lines: 8,
+        };
+        assert_eq!(function.to_bytes(), Some([0x69, 0x7F, 0x00]));
+    }
+
+    #[test]
+    fn dfc_rejects_bad_line_counts() {
+        let function = DisplayFunction::default();
+        assert_eq!(DisplayFunction { lines: 0, ..function }.to_bytes(), None);
+        assert_eq!(DisplayFunction { lines: 100, ..function }.to_bytes(), None);
+        assert_eq!(DisplayFunction { lines: 488, ..function }.to_bytes(), None);
+        assert_eq!(DisplayFunction { lines: 320, ..function }.to_bytes(), Some([0x80, 0x02, 0x27]));
+    }
+
+    #[test]
+    fn isc_selects_odd_frame_counts() {
+        let function = DisplayFunction::default();
+        assert_eq!(function.scan_cycle_frames(), Some(5));
+        assert_eq!(DisplayFunction { isc: 1, ..function }.scan_cycle_frames(), Some(3));
+        assert_eq!(DisplayFunction { isc: 0x0F, ..function }.scan_cycle_frames(), Some(31));
+        assert_eq!(DisplayFunction { isc: 0, ..function }.scan_cycle_frames(), None);
+        assert_eq!(DisplayFunction { isc: 0x10, ..function }.scan_cycle_frames(), None);
+        assert_eq!(DisplayFunction { isc: 0, ..function }.to_bytes(), None);
+        assert_eq!(DisplayFunction { isc: 0x10, ..function }.to_bytes(), None);
+    }
+
+    #[test]
+    fn em_packs_expansion_and_gate_output() {
+        assert_eq!(EntryMode::default().to_bytes(), [0x06]);
+        let mode = EntryMode { expansion: ColorExpansion::ONE, gate_output: GateOutput::VGL };
+        assert_eq!(mode.to_bytes(), [0x44]);
+        let mode = EntryMode { expansion: ColorExpansion::GREEN, gate_output: GateOutput::VGH };
+        assert_eq!(mode.to_bytes(), [0xC0]);
+        let mode = EntryMode { expansion: ColorExpansion::MSB, ..EntryMode::default() };
+        assert_eq!(mode.to_bytes(), [0x86]);
+    }
+}